@@ -0,0 +1,47 @@
+use std::fs;
+
+/// The migration directories, one per database backend.
+const MIGRATION_DIRS: &[&str] = &[
+    "migrations/mysql",
+    "migrations/postgres",
+    "migrations/sqlite",
+];
+
+/// Expose the schema version of the newest embedded migration as `SCHEMA_VERSION`.
+///
+/// Diesel derives the version of a migration from its directory name, such as
+/// `2018-07-14-112340_create_chats`, by dropping the dashes from the part before the first `_`.
+/// The newest version must be the same for all backends.
+fn main() {
+    let versions: Vec<String> = MIGRATION_DIRS
+        .iter()
+        .map(|dir| {
+            println!("cargo:rerun-if-changed={}", dir);
+            newest_version(dir)
+        })
+        .collect();
+    if versions.iter().any(|version| version != &versions[0]) {
+        panic!(
+            "newest migration differs between backends {:?}: {:?}",
+            MIGRATION_DIRS, versions
+        );
+    }
+    println!("cargo:rustc-env=SCHEMA_VERSION={}", versions[0]);
+}
+
+/// Get the version of the newest migration in the given directory.
+fn newest_version(dir: &str) -> String {
+    fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("failed to read migrations in {}: {}", dir, err))
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if !entry.file_type().ok()?.is_dir() {
+                return None;
+            }
+            let name = entry.file_name().into_string().ok()?;
+            let version = name.split('_').next()?.replace('-', "");
+            Some(version)
+        })
+        .max()
+        .unwrap_or_else(|| panic!("no migrations in {}", dir))
+}
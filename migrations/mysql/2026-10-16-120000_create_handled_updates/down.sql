@@ -0,0 +1 @@
+DROP TABLE handled_update;
@@ -0,0 +1,6 @@
+-- Updates claimed for handling, to resume polling and to handle each update at most once
+CREATE TABLE handled_update (
+  update_id     BIGINT          PRIMARY KEY,
+  handled_at    DATETIME        NOT NULL
+    DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Custom command aliases of each chat
+CREATE TABLE chat_alias (
+  chat_id       BIGINT          NOT NULL,
+  alias         VARCHAR(32)     NOT NULL,
+  command       VARCHAR(32)     NOT NULL,
+  created_at    DATETIME        NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+
+  PRIMARY KEY (chat_id, alias)
+);
@@ -0,0 +1,10 @@
+-- Features and actions toggled in each chat, features without a row are enabled
+CREATE TABLE chat_settings (
+  chat_id       BIGINT          NOT NULL,
+  feature       VARCHAR(32)     NOT NULL,
+  enabled       BOOLEAN         NOT NULL,
+  updated_at    DATETIME        NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+
+  PRIMARY KEY (chat_id, feature)
+);
@@ -0,0 +1,2 @@
+DROP TABLE chat;
+DROP FUNCTION set_updated_at();
@@ -0,0 +1,22 @@
+-- Keep updated_at current on row updates, like ON UPDATE CURRENT_TIMESTAMP in MySQL
+CREATE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+  IF NEW IS DISTINCT FROM OLD AND NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at THEN
+    NEW.updated_at := CURRENT_TIMESTAMP;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TABLE chat (
+  telegram_id   BIGINT          PRIMARY KEY,
+  title         VARCHAR(255),
+  created_at    TIMESTAMP       NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    TIMESTAMP       NOT NULL
+    DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER chat_updated_at
+  BEFORE UPDATE ON chat
+  FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
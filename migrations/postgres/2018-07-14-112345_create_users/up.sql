@@ -0,0 +1,13 @@
+CREATE TABLE "user" (
+  telegram_id   BIGINT      PRIMARY KEY,
+  first_name    TEXT        NOT NULL,
+  last_name     TEXT        DEFAULT NULL,
+  created_at    TIMESTAMP   NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    TIMESTAMP   NOT NULL
+    DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER user_updated_at
+  BEFORE UPDATE ON "user"
+  FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
@@ -0,0 +1,23 @@
+CREATE TABLE chat_user_stats (
+  chat_id       BIGINT      NOT NULL,
+  user_id       BIGINT      NOT NULL,
+  message_type  SMALLINT    NOT NULL,
+  messages      INT         NOT NULL,
+  edits         INT         NOT NULL,
+  created_at    TIMESTAMP   NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    TIMESTAMP   NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+
+  PRIMARY KEY (chat_id, user_id, message_type),
+  FOREIGN KEY (chat_id)
+    REFERENCES chat(telegram_id)
+    ON DELETE CASCADE,
+  FOREIGN KEY (user_id)
+    REFERENCES "user"(telegram_id)
+    ON DELETE CASCADE
+);
+
+CREATE TRIGGER chat_user_stats_updated_at
+  BEFORE UPDATE ON chat_user_stats
+  FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
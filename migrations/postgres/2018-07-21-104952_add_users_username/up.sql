@@ -0,0 +1,3 @@
+ALTER TABLE "user"
+ADD COLUMN
+    username TEXT DEFAULT NULL;
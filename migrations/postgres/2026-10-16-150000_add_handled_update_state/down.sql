@@ -0,0 +1,2 @@
+ALTER TABLE handled_update
+DROP COLUMN handled;
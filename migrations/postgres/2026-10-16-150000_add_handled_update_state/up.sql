@@ -0,0 +1,4 @@
+-- Updates are recorded when received, and marked handled once handling completes
+ALTER TABLE handled_update
+ADD COLUMN
+    handled BOOLEAN NOT NULL DEFAULT TRUE;
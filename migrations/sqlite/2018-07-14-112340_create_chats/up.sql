@@ -0,0 +1,17 @@
+CREATE TABLE chat (
+  telegram_id   BIGINT          PRIMARY KEY,
+  title         VARCHAR(255),
+  created_at    DATETIME        NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    DATETIME        NOT NULL
+    DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Keep updated_at current on row updates, like ON UPDATE CURRENT_TIMESTAMP in MySQL
+CREATE TRIGGER chat_updated_at
+  AFTER UPDATE ON chat
+  FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE chat SET updated_at = CURRENT_TIMESTAMP
+    WHERE telegram_id = NEW.telegram_id;
+END;
@@ -0,0 +1,17 @@
+CREATE TABLE user (
+  telegram_id   BIGINT      PRIMARY KEY,
+  first_name    TEXT        NOT NULL,
+  last_name     TEXT        DEFAULT NULL,
+  created_at    DATETIME    NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    DATETIME    NOT NULL
+    DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER user_updated_at
+  AFTER UPDATE ON user
+  FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE user SET updated_at = CURRENT_TIMESTAMP
+    WHERE telegram_id = NEW.telegram_id;
+END;
@@ -0,0 +1,29 @@
+CREATE TABLE chat_user_stats (
+  chat_id       BIGINT      NOT NULL,
+  user_id       BIGINT      NOT NULL,
+  message_type  SMALLINT    NOT NULL,
+  messages      INT         NOT NULL,
+  edits         INT         NOT NULL,
+  created_at    DATETIME    NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+  updated_at    DATETIME    NOT NULL
+    DEFAULT CURRENT_TIMESTAMP,
+
+  PRIMARY KEY (chat_id, user_id, message_type),
+  FOREIGN KEY (chat_id)
+    REFERENCES chat(telegram_id)
+    ON DELETE CASCADE,
+  FOREIGN KEY (user_id)
+    REFERENCES user(telegram_id)
+    ON DELETE CASCADE
+);
+
+CREATE TRIGGER chat_user_stats_updated_at
+  AFTER UPDATE ON chat_user_stats
+  FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE chat_user_stats SET updated_at = CURRENT_TIMESTAMP
+    WHERE chat_id = NEW.chat_id
+      AND user_id = NEW.user_id
+      AND message_type = NEW.message_type;
+END;
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{self, PanicHookInfo};
+use std::sync::{Mutex, TryLockError};
+
+use futures::prelude::*;
+use telegram_bot::{prelude::*, types::ChatId};
+use tokio_stream::wrappers::IntervalStream;
+
+use crate::state::State;
+use crate::util::panic_message;
+
+/// The maximum length of an alert message, to stay within Telegram limits.
+const MAX_LENGTH: usize = 4000;
+
+/// The kind of an operator alert.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AlertKind {
+    /// An action failed.
+    Action,
+
+    /// Stats failed to flush to the database.
+    StatsFlush,
+
+    /// No database connection could be obtained from the pool.
+    Database,
+
+    /// A user command could not be spawned by the executor.
+    ExecutorSpawn,
+
+    /// The bot panicked.
+    Panic,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AlertKind::Action => "action failed",
+            AlertKind::StatsFlush => "stats flush failed",
+            AlertKind::Database => "database unavailable",
+            AlertKind::ExecutorSpawn => "executor spawn failed",
+            AlertKind::Panic => "panic",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Collects operator alerts, to send them grouped to the operator chat.
+///
+/// Alerts of the same kind and detail are grouped into a single incident with a count, and sent
+/// once per interval. Nothing is collected if no operator chat is configured.
+pub struct Alerts {
+    /// Whether alerts are collected.
+    enabled: bool,
+
+    /// The number of occurrences of each incident since alerts were last sent.
+    pending: Mutex<HashMap<(AlertKind, String), usize>>,
+}
+
+impl Alerts {
+    /// Construct a new alert collector, collecting alerts only if enabled.
+    pub fn new(enabled: bool) -> Self {
+        Alerts {
+            enabled,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an alert of the given kind, with a detail to group it by.
+    ///
+    /// Waits for concurrent alerts to be recorded, use `record_nonblocking` while panicking.
+    pub fn record(&self, kind: AlertKind, detail: impl Into<String>) {
+        if !self.enabled {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        *pending.entry((kind, detail.into())).or_insert(0) += 1;
+    }
+
+    /// Record an alert like `record`, but drop it if alerts are being recorded concurrently.
+    ///
+    /// This never blocks, so it is safe to use in the panic hook, which may run while the current
+    /// thread is recording an alert.
+    fn record_nonblocking(&self, kind: AlertKind, detail: impl Into<String>) {
+        if !self.enabled {
+            return;
+        }
+        match self.pending.try_lock() {
+            Ok(mut pending) => *pending.entry((kind, detail.into())).or_insert(0) += 1,
+            Err(TryLockError::Poisoned(err)) => {
+                *err.into_inner().entry((kind, detail.into())).or_insert(0) += 1
+            }
+            Err(TryLockError::WouldBlock) => {}
+        }
+    }
+
+    /// Put the given incidents back, after failing to send them.
+    ///
+    /// Their counts are added to incidents recorded in the meantime.
+    fn restore(&self, incidents: Vec<(AlertKind, String, usize)>) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        for (kind, detail, count) in incidents {
+            *pending.entry((kind, detail)).or_insert(0) += count;
+        }
+    }
+
+    /// Take all pending incidents, ordered by kind.
+    fn take(&self) -> Vec<(AlertKind, String, usize)> {
+        let mut incidents: Vec<_> = self
+            .pending
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .drain()
+            .map(|((kind, detail), count)| (kind, detail, count))
+            .collect();
+        incidents.sort();
+        incidents
+    }
+}
+
+/// Build a future sending pending alerts to the operator chat, once each interval.
+///
+/// Returned future never completes.
+pub fn build_sender(state: State) -> impl Future<Output = ()> {
+    let interval = tokio::time::interval(state.config().alerts.interval());
+    IntervalStream::new(interval).for_each(move |_| send_pending(state.clone()))
+}
+
+/// Send all pending alerts to the operator chat as a single message.
+///
+/// If sending fails, the alerts are kept to send them again the next interval.
+async fn send_pending(state: State) {
+    let chat = match state.config().alerts.chat {
+        Some(chat) => ChatId::new(chat),
+        None => return,
+    };
+    let incidents = state.alerts().take();
+    if incidents.is_empty() {
+        return;
+    }
+
+    let mut text = String::from("⚠️ Operator alert");
+    for (kind, detail, count) in &incidents {
+        text += &format!("\n\n• {} ({}×)\n{}", kind, count, detail);
+    }
+    if text.chars().count() > MAX_LENGTH {
+        text = text.chars().take(MAX_LENGTH - 1).collect::<String>() + "…";
+    }
+
+    if let Err(err) = state.send(chat.text(text)).await {
+        warn!(%err, "failed to send operator alert, retrying next interval");
+        state.alerts().restore(incidents);
+    }
+}
+
+/// Install a panic hook reporting panics as operator alert, before the default panic handling.
+pub fn install_panic_hook(state: State) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let detail = describe_panic(info);
+        error!(panic = %detail, "panicked");
+        state.alerts().record_nonblocking(AlertKind::Panic, detail);
+        previous(info);
+    }));
+}
+
+/// Describe the given panic, with its message and location.
+fn describe_panic(info: &PanicHookInfo) -> String {
+    let message = panic_message(info.payload());
+    match info.location() {
+        Some(location) => format!("{} at {}:{}", message, location.file(), location.line()),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{send_pending, AlertKind, Alerts};
+    use crate::config::Config;
+    use crate::testing::{self, Harness, GROUP_ID, PRIVATE_ID};
+
+    /// Set up a bot sending alerts to the private chat with the test user.
+    async fn harness() -> Harness {
+        let mut config = Config::default();
+        config.alerts.chat = Some(PRIVATE_ID);
+        Harness::with_config(config).await
+    }
+
+    #[tokio::test]
+    async fn groups_failed_actions_by_command_and_error() {
+        let harness = harness().await;
+        for description in &[
+            "Bad Request: chat not found",
+            "Bad Request: message is too long",
+        ] {
+            harness
+                .transport
+                .script_error("sendMessage", 400, description);
+            assert!(harness
+                .handle(testing::message(GROUP_ID, "/ping"))
+                .await
+                .is_err());
+        }
+        harness.transport.clear();
+
+        send_pending(harness.state.clone()).await;
+        assert_eq!(
+            harness.sent_texts(),
+            vec![
+                "⚠️ Operator alert\n\n• action failed (2×)\n/ping: failed to send response message"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_alerts_that_failed_to_send() {
+        let harness = harness().await;
+        let alerts = harness.state.alerts();
+        alerts.record(AlertKind::Database, "timed out");
+        harness
+            .transport
+            .script_error("sendMessage", 400, "Bad Request: chat not found");
+        send_pending(harness.state.clone()).await;
+
+        alerts.record(AlertKind::Database, "timed out");
+        send_pending(harness.state.clone()).await;
+        assert_eq!(
+            harness.sent_texts()[1],
+            "⚠️ Operator alert\n\n• database unavailable (2×)\ntimed out"
+        );
+
+        // Sent alerts are not sent again
+        send_pending(harness.state.clone()).await;
+        assert_eq!(harness.transport.sent().len(), 2);
+    }
+
+    #[test]
+    fn records_alerts_under_contention() {
+        let alerts = Arc::new(Alerts::new(true));
+        let pending = alerts.pending.lock().unwrap();
+        let recorder = {
+            let alerts = alerts.clone();
+            thread::spawn(move || alerts.record(AlertKind::Database, "timed out"))
+        };
+        thread::sleep(Duration::from_millis(50));
+        drop(pending);
+        recorder.join().unwrap();
+
+        assert_eq!(
+            alerts.take(),
+            vec![(AlertKind::Database, "timed out".to_owned(), 1)]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use diesel::prelude::*;
+
+use super::Error;
+use crate::config::MigrationMode;
+use crate::db::DbPool;
+use crate::migrations;
+use crate::models::Chat;
+use crate::schema::chat;
+
+/// List all chats known to the bot, most recently active first.
+pub fn list() -> Result<(), Error> {
+    use self::chat::dsl::updated_at;
+
+    let db = DbPool::from_env()?;
+    migrations::prepare(&db, MigrationMode::Check)?;
+
+    // Fetch all chats
+    let connection = db.get().map_err(Error::Pool)?;
+    let chats = with_connection!(connection, |conn| chat::table
+        .order(updated_at.desc())
+        .load::<Chat>(conn))?;
+
+    // Report the chats
+    for chat in &chats {
+        println!(
+            "{}\t{}\tfirst seen {}\tlast active {}",
+            chat.telegram_id,
+            chat.title.as_deref().unwrap_or("(private)"),
+            chat.created_at,
+            chat.updated_at,
+        );
+    }
+    println!("{} chats", chats.len());
+
+    Ok(())
+}
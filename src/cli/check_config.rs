@@ -0,0 +1,31 @@
+use std::env;
+
+use super::Error;
+use crate::config::{Config, TransportKind};
+use crate::db::Backend;
+
+/// Check the configuration and environment.
+///
+/// The configuration itself is already loaded and validated before this is invoked, this
+/// additionally checks the environment variables the bot requires to run.
+pub fn invoke(config: &Config) -> Result<(), Error> {
+    match Config::path() {
+        Some(path) => println!("Configuration: {}", path.display()),
+        None => println!("Configuration: defaults, no file found"),
+    }
+
+    // The database URL must select a known backend
+    let database_url =
+        env::var("DATABASE_URL").map_err(|_| Error::Env("DATABASE_URL not set".into()))?;
+    let (backend, _) = Backend::from_url(&database_url)
+        .ok_or_else(|| Error::Env("DATABASE_URL has an unknown database backend".into()))?;
+    println!("Database backend: {}", backend);
+
+    // A bot token is required unless a fake transport is used
+    if config.telegram.transport == TransportKind::Api && env::var("TELEGRAM_BOT_TOKEN").is_err() {
+        return Err(Error::Env("TELEGRAM_BOT_TOKEN not set".into()));
+    }
+
+    println!("Configuration is valid");
+    Ok(())
+}
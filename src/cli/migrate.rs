@@ -0,0 +1,28 @@
+use super::Error;
+use crate::db::DbPool;
+use crate::migrations::{self, Error as MigrationError, SCHEMA_VERSION};
+
+/// Apply pending database migrations.
+///
+/// If `check` is set, no migrations are applied and this fails if any are pending.
+pub fn invoke(check: bool) -> Result<(), Error> {
+    let db = DbPool::from_env()?;
+
+    // Report pending migrations, apply them if not checking
+    let current = migrations::schema_version(&db)?;
+    let up_to_date = current.as_deref().map_or(false, |v| v >= SCHEMA_VERSION);
+    if !up_to_date {
+        if check {
+            return Err(MigrationError::Pending { current }.into());
+        }
+        migrations::run(&db)?;
+    }
+
+    let version = migrations::schema_version(&db)?;
+    println!(
+        "Database schema version: {} ({})",
+        version.as_deref().unwrap_or("empty"),
+        db.backend(),
+    );
+    Ok(())
+}
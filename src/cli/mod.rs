@@ -0,0 +1,182 @@
+pub mod chats;
+pub mod check_config;
+pub mod migrate;
+pub mod send;
+pub mod stats;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use diesel::{r2d2::PoolError, result::Error as DieselError};
+
+use crate::app;
+use crate::db::Error as DbError;
+use crate::migrations::Error as MigrationError;
+use crate::send::Error as SendError;
+
+/// A command selected on the command line.
+pub enum Command {
+    /// Run the bot, the default if no subcommand is given.
+    Run,
+
+    /// Apply pending database migrations, or only check for them.
+    Migrate { check: bool },
+
+    /// Check the configuration and environment.
+    CheckConfig,
+
+    /// Dump the message stats of a chat, optionally with the stats of a specific user.
+    StatsDump { chat: i64, user: Option<i64> },
+
+    /// List all known chats.
+    ChatsList,
+
+    /// Send a text message to a chat.
+    Send { chat: i64, text: String },
+}
+
+impl Command {
+    /// Parse the command to invoke from the command line arguments.
+    ///
+    /// On invalid arguments, this prints usage information and exits the process.
+    pub fn from_args() -> Command {
+        Self::from_matches(&build_app().get_matches())
+    }
+
+    /// Select the command from the given argument matches.
+    fn from_matches(matches: &ArgMatches) -> Command {
+        match matches.subcommand() {
+            ("migrate", Some(matches)) => Command::Migrate {
+                check: matches.is_present("check"),
+            },
+            ("check-config", Some(_)) => Command::CheckConfig,
+            ("stats", Some(matches)) => match matches.subcommand() {
+                ("dump", Some(matches)) => Command::StatsDump {
+                    chat: parse_id(matches, "chat").unwrap(),
+                    user: parse_id(matches, "user"),
+                },
+                _ => unreachable!(),
+            },
+            ("chats", Some(matches)) => match matches.subcommand() {
+                ("list", Some(_)) => Command::ChatsList,
+                _ => unreachable!(),
+            },
+            ("send", Some(matches)) => Command::Send {
+                chat: parse_id(matches, "chat").unwrap(),
+                text: matches.value_of("TEXT").unwrap().into(),
+            },
+            _ => Command::Run,
+        }
+    }
+}
+
+/// Build the command line interface definition.
+fn build_app() -> App<'static, 'static> {
+    App::new(app::NAME)
+        .version(app::VERSION)
+        .about("A Telegram bot, and tools to operate it")
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(SubCommand::with_name("run").about("Run the bot (default)"))
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Apply pending database migrations")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Only check for pending migrations, fail if there are any"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check-config")
+                .about("Check the configuration and environment, then exit"),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Inspect message stats")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("dump")
+                        .about("Dump the message stats of a chat")
+                        .arg(id_arg("chat").required(true).help("The chat ID"))
+                        .arg(id_arg("user").help("Include stats of the user with this ID")),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("chats")
+                .about("Inspect known chats")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("List all known chats")),
+        )
+        .subcommand(
+            SubCommand::with_name("send")
+                .about("Send a text message to a chat as the bot")
+                .arg(id_arg("chat").required(true).help("The chat ID to send to"))
+                .arg(
+                    Arg::with_name("TEXT")
+                        .required(true)
+                        .help("The message text to send"),
+                ),
+        )
+}
+
+/// Build an option argument taking a Telegram ID, which may be negative.
+fn id_arg(name: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name("ID")
+        .allow_hyphen_values(true)
+        .validator(|id| {
+            id.parse::<i64>()
+                .map(|_| ())
+                .map_err(|_| format!("'{}' is not a valid ID", id))
+        })
+}
+
+/// Parse a validated ID argument if present.
+fn parse_id(matches: &ArgMatches, name: &str) -> Option<i64> {
+    matches.value_of(name).map(|id| id.parse().unwrap())
+}
+
+/// A command line interface error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to connect to the database.
+    #[fail(display = "failed to connect to database")]
+    Database(#[cause] DbError),
+
+    /// Failed to get a database connection from the pool.
+    #[fail(display = "failed to get database connection from pool")]
+    Pool(#[cause] PoolError),
+
+    /// The database schema is not ready to be used.
+    #[fail(display = "failed to prepare database schema")]
+    Migrate(#[cause] MigrationError),
+
+    /// Failed to query the database.
+    #[fail(display = "failed to query database")]
+    Query(#[cause] DieselError),
+
+    /// Failed to send a message.
+    #[fail(display = "failed to send message")]
+    Send(#[cause] SendError),
+
+    /// The configuration or environment is invalid.
+    #[fail(display = "invalid environment: {}", _0)]
+    Env(String),
+}
+
+impl From<DbError> for Error {
+    fn from(err: DbError) -> Error {
+        Error::Database(err)
+    }
+}
+
+impl From<MigrationError> for Error {
+    fn from(err: MigrationError) -> Error {
+        Error::Migrate(err)
+    }
+}
+
+impl From<DieselError> for Error {
+    fn from(err: DieselError) -> Error {
+        Error::Query(err)
+    }
+}
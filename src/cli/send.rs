@@ -0,0 +1,23 @@
+use telegram_bot::{prelude::*, types::ChatId};
+use tokio::runtime::Handle;
+
+use super::Error;
+use crate::config::Config;
+use crate::state::State;
+
+/// Send a text message to the given chat as the bot.
+///
+/// The sent message is counted in the message stats like any other message the bot sends.
+pub async fn invoke(config: Config, chat: i64, text: String) -> Result<(), Error> {
+    let state = State::init(config, Handle::current()).await;
+
+    // Send the message
+    let result = state.send(ChatId::new(chat).text(text)).await;
+
+    // Flush the stats of the sent message before quitting
+    state.stats().flush(state.db());
+
+    result.map_err(Error::Send)?;
+    println!("Message sent to chat {}", chat);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use telegram_bot::types::{ChatId, UserId};
+
+use super::Error;
+use crate::config::MigrationMode;
+use crate::db::DbPool;
+use crate::migrations;
+use crate::stats::Stats;
+
+/// Dump the message stats of the given chat, optionally with stats of the given user.
+///
+/// Only stats flushed to the database are included.
+pub fn dump(chat: i64, user: Option<i64>) -> Result<(), Error> {
+    let db = DbPool::from_env()?;
+    migrations::prepare(&db, MigrationMode::Check)?;
+
+    // Fetch the chat stats
+    let connection = db.get().map_err(Error::Pool)?;
+    let stats =
+        Stats::new().fetch_chat_stats(connection, ChatId::new(chat), user.map(UserId::new))?;
+
+    // Report the user totals
+    println!("Messages (edits) in chat {}:", chat);
+    for (i, (name, id, username, messages, edits)) in stats.users().iter().enumerate() {
+        match username {
+            Some(username) if !username.is_empty() => println!(
+                "{}. {} (@{}, {}): {} ({})",
+                i + 1,
+                name,
+                username,
+                id,
+                messages,
+                edits,
+            ),
+            _ => println!("{}. {} ({}): {} ({})", i + 1, name, id, messages, edits),
+        }
+    }
+
+    // Report the user specifics if available
+    if let (Some(user), Some(specific)) = (user, stats.specific()) {
+        println!("\nMessages (edits) of user {}:", user);
+        for (kind, messages, edits) in specific {
+            println!("{}: {} ({})", kind.name(), messages, edits);
+        }
+    }
+
+    // Report other stats
+    println!(
+        "\nTotal: {} ({})",
+        stats.total_messages(),
+        stats.total_edits(),
+    );
+    if let Some(since) = stats.since() {
+        println!("Since: {}", since);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use async_trait::async_trait;
+use failure::Error as FailureError;
+use futures::prelude::*;
+use htmlescape::encode_minimal;
+use telegram_bot::{
+    prelude::*,
+    types::{Message, ParseMode},
+};
+
+use super::{Action, ACTIONS};
+use crate::cmd::alias;
+use crate::cmd::args::{Arg, Args};
+use crate::cmd::permission::Role;
+use crate::db::QueryError;
+use crate::send::Error as SendError;
+use crate::state::State;
+use crate::stats::TelegramToI64;
+
+/// The action command name.
+const CMD: &str = "alias";
+
+/// Whether the action is hidden.
+const HIDDEN: bool = false;
+
+/// The action help.
+const HELP: &str = "Manage command aliases for this chat";
+
+/// The role required to invoke the action.
+const ROLE: Role = Role::ChatAdmin;
+
+/// The action arguments.
+const ARGS: &[Arg] = &[
+    Arg::positional("alias", "The alias to set or remove").optional(),
+    Arg::positional("command", "The command the alias runs").optional(),
+    Arg::flag("remove", "Remove the alias"),
+];
+
+/// The action description.
+const DESCRIPTION: &str = "Without arguments, lists the aliases of this chat. With only an \
+                           alias, shows the command it runs. Aliases can't replace an existing \
+                           command.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/alias", "/alias s duck", "/alias s --remove"];
+
+pub struct Alias;
+
+impl Alias {
+    pub fn new() -> Self {
+        Alias
+    }
+
+    /// Build the response for the given arguments, managing the chat aliases.
+    async fn respond(state: &State, chat: i64, args: &Args) -> Result<String, QueryError> {
+        let name = match args.get("alias") {
+            Some(name) => name.trim_start_matches('/'),
+            None => return Self::list(state, chat).await,
+        };
+        let shown = encode_minimal(&alias::normalize(name));
+
+        // Remove the alias
+        if args.flag("remove") {
+            return Ok(if alias::remove(state, chat, name).await? {
+                format!("Removed alias /{}.", shown)
+            } else {
+                format!("There is no alias /{}.", shown)
+            });
+        }
+
+        // Show the alias if no command is given
+        let command = match args.get("command") {
+            Some(command) => command.trim_start_matches('/'),
+            None => {
+                return Ok(match alias::resolve(state, chat, name).await? {
+                    Some(command) => format!("/{} runs /{}.", shown, command),
+                    None => format!("There is no alias /{}.", shown),
+                });
+            }
+        };
+
+        // Validate the alias and the command, then set the alias
+        if !alias::is_valid(name) {
+            return Ok(format!(
+                "An alias may only contain letters, digits and underscores, and must be at most {} characters.",
+                alias::MAX_LENGTH,
+            ));
+        }
+        if ACTIONS.iter().any(|action| action.is_cmd(name)) {
+            return Ok(format!("/{} is already a command.", shown));
+        }
+        let action = match ACTIONS.iter().find(|action| action.is_cmd(command)) {
+            Some(action) => action,
+            None => {
+                return Ok(format!(
+                    "There is no command /{}.",
+                    encode_minimal(&alias::normalize(command))
+                ))
+            }
+        };
+        alias::set(state, chat, name, action.cmd()).await?;
+        Ok(format!("/{} now runs /{}.", shown, action.cmd()))
+    }
+
+    /// List the aliases of the given chat.
+    async fn list(state: &State, chat: i64) -> Result<String, QueryError> {
+        let aliases = alias::list(state, chat).await?;
+        if aliases.is_empty() {
+            return Ok(format!(
+                "This chat has no aliases, add one with <code>/{} &lt;alias&gt; &lt;command&gt;</code>.",
+                CMD
+            ));
+        }
+
+        let aliases: Vec<String> = aliases
+            .into_iter()
+            .map(|(alias, command)| format!("/{}: /{}", alias, command))
+            .collect();
+        Ok(format!("<b>Aliases:</b>\n{}", aliases.join("\n")))
+    }
+}
+
+#[async_trait]
+impl Action for Alias {
+    fn cmd(&self) -> &'static str {
+        CMD
+    }
+
+    fn hidden(&self) -> bool {
+        HIDDEN
+    }
+
+    fn help(&self) -> &'static str {
+        HELP
+    }
+
+    fn role(&self) -> Role {
+        ROLE
+    }
+
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
+        let chat = msg.chat.id().to_i64();
+        let response = Self::respond(&state, chat, &args)
+            .await
+            .map_err(Error::Alias)?;
+
+        // Build a future for sending the response message
+        state
+            .send(msg.text_reply(response).parse_mode(ParseMode::Html))
+            .map_ok(|_| ())
+            .map_err(|err| Error::Respond(err).into())
+            .await
+    }
+}
+
+/// An alias action error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// An error occurred while managing the chat aliases.
+    #[fail(display = "failed to manage chat aliases")]
+    Alias(#[cause] QueryError),
+
+    /// An error occurred while sending a response message to the user.
+    #[fail(display = "failed to send response message")]
+    Respond(#[cause] SendError),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::testing::{self, Harness, GROUP_ID, PRIVATE_ID, USER_ID};
+
+    /// Handle the given commands in the given chat, and get the texts of all replies.
+    async fn replies(harness: &Harness, chat: i64, cmds: &[&str]) -> Vec<String> {
+        for cmd in cmds {
+            harness.handle(testing::message(chat, cmd)).await.unwrap();
+        }
+        harness.sent_texts()
+    }
+
+    #[tokio::test]
+    async fn sets_runs_and_removes_aliases() {
+        let harness = Harness::new().await;
+        let texts = replies(
+            &harness,
+            PRIVATE_ID,
+            &[
+                "/alias",
+                "/alias P /ping",
+                "/p",
+                "/P",
+                "/alias",
+                "/alias p",
+                "/alias p --remove",
+                "/p",
+                "/alias p --remove",
+            ],
+        )
+        .await;
+        assert_eq!(
+            texts,
+            vec![
+                "This chat has no aliases, add one with <code>/alias &lt;alias&gt; &lt;command&gt;</code>.",
+                "/p now runs /ping.",
+                "Pong!",
+                "Pong!",
+                "<b>Aliases:</b>\n/p: /ping",
+                "/p runs /ping.",
+                "Removed alias /p.",
+                "There is no alias /p.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_aliases() {
+        let harness = Harness::new().await;
+        let texts = replies(
+            &harness,
+            PRIVATE_ID,
+            &["/alias ping duck", "/alias p nope", "/alias p-q ping"],
+        )
+        .await;
+        assert_eq!(texts[0], "/ping is already a command.");
+        assert_eq!(texts[1], "There is no command /nope.");
+        assert!(
+            texts[2].starts_with("An alias may only contain"),
+            "{}",
+            texts[2]
+        );
+    }
+
+    #[tokio::test]
+    async fn aliases_are_per_chat() {
+        let mut config = Config::default();
+        config.bot.owners.push(USER_ID);
+        let harness = Harness::with_config(config).await;
+        let texts = replies(&harness, GROUP_ID, &["/alias p ping", "/p"]).await;
+        assert_eq!(texts, vec!["/p now runs /ping.", "Pong!"]);
+
+        harness.transport.clear();
+        assert!(replies(&harness, PRIVATE_ID, &["/p"]).await.is_empty());
+    }
+}
@@ -1,13 +1,16 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
 use super::Action;
+use crate::cmd::args::Args;
+use crate::cmd::cooldown::Cooldown;
+use crate::cmd::permission::Role;
+use crate::send::Error as SendError;
 use crate::state::State;
 use crate::stats::TelegramToI64;
 
@@ -20,6 +23,12 @@ const HIDDEN: bool = false;
 /// The action help.
 const HELP: &str = "Mention all members";
 
+/// The role required to invoke the action.
+const ROLE: Role = Role::ChatAdmin;
+
+/// The action cooldowns.
+const COOLDOWNS: &[Cooldown] = &[Cooldown::per_chat(1, 300)];
+
 pub struct All;
 
 impl All {
@@ -42,7 +51,15 @@ impl Action for All {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    fn role(&self) -> Role {
+        ROLE
+    }
+
+    fn cooldowns(&self) -> &'static [Cooldown] {
+        COOLDOWNS
+    }
+
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Fetch the chat message stats
         let stats = match state.stats().fetch_chat_stats(
             state.db_connection(),
@@ -53,21 +70,21 @@ impl Action for All {
             Err(e) => return Err(e.into()),
         };
 
-        // Create a list of user mentions
+        // Create a list of user mentions, skip the sender and the bot itself
         // TODO: limit mentions to 100 users max?
-        // TODO: do not mention the bot itself
         // TODO: do not mention users not in this group anymore
+        let bot_id = state.bot().id.to_i64();
         let mentions = stats
             .users()
             .iter()
-            .filter(|(_, user_id, _, _, _)| *user_id != msg.from.id.to_i64())
+            .filter(|(_, user_id, _, _, _)| *user_id != msg.from.id.to_i64() && *user_id != bot_id)
             .map(|(_, user_id, _, _, _)| format!("[@](tg://user?id={})", user_id))
             .collect::<Vec<String>>()
             .join(" ");
 
         // Build a message future for sending the response
         state
-            .telegram_send(
+            .send(
                 msg.text_reply(format!(
                     "*Attention!* [{}](tg://user?id={}) mentions #all users.\n{}",
                     msg.from.first_name, msg.from.id, mentions,
@@ -75,7 +92,7 @@ impl Action for All {
                 .parse_mode(ParseMode::Markdown),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -85,5 +102,5 @@ impl Action for All {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
@@ -1,24 +1,39 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageKind, ParseMode},
-    Error as TelegramError,
 };
 
+use super::help::build_cmd_help;
 use super::Action;
+use crate::cmd::args::{Arg, Args};
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
 const CMD: &str = "duck";
 
+/// The action command aliases.
+const ALIASES: &[&str] = &["search", "ddg"];
+
 /// Whether the action is hidden.
 const HIDDEN: bool = false;
 
 /// The action help.
 const HELP: &str = "Search using DuckDuckGo";
 
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::rest("query", "The search query").optional()];
+
+/// The action description.
+const DESCRIPTION: &str = "Replies with a link to the search results on DuckDuckGo. Bangs are \
+                           supported, such as !w to search Wikipedia.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/duck Telegram", "/duck !w Telegram app"];
+
 /// Base URL, to append the search query to.
 const URL: &str = "https://duckduckgo.com/?q=";
 
@@ -36,6 +51,10 @@ impl Action for Duck {
         CMD
     }
 
+    fn aliases(&self) -> &'static [&'static str] {
+        ALIASES
+    }
+
     fn hidden(&self) -> bool {
         HIDDEN
     }
@@ -44,41 +63,49 @@ impl Action for Duck {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
-        if let MessageKind::Text { ref data, .. } = &msg.kind {
-            // Get the user's input
-            // TODO: actually properly fetch the user input
-            let input = data
-                .splitn(2, ' ')
-                .nth(1)
-                .map(|cmd| cmd.trim_start())
-                .unwrap_or("")
-                .trim()
-                .to_owned();
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
 
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
+        if let MessageKind::Text { .. } = &msg.kind {
             // Make sure something was entered
-            if input.is_empty() {
-                // Build a message future for sending the response
-                return state
-                    .telegram_send(msg.text_reply("Search using [DuckDuckGo](https://duckduckgo.com/).\n\nPlease provide a search query, such as:\n`/duck Telegram`\n`/duck !w Telegram app`").parse_mode(ParseMode::Markdown).disable_preview())
-                    .map_ok(|_| ())
-                    .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
-                    .await;
-            }
+            let input = match args.get("query") {
+                Some(input) => input,
+                None => {
+                    // Build a message future for sending the response
+                    return state
+                        .send(
+                            msg.text_reply(build_cmd_help(&state, self))
+                                .parse_mode(ParseMode::Html),
+                        )
+                        .map_ok(|_| ())
+                        .map_err(|err| Error::Respond(err).into())
+                        .await;
+                }
+            };
 
             // Build the search URL, build the response
-            let url = format!("{}{}", URL, urlencoding::encode(&input));
+            let url = format!("{}{}", URL, urlencoding::encode(input));
             let response = format!(
                 "<a href=\"{}\">{}</a>",
                 url,
-                htmlescape::encode_minimal(&input)
+                htmlescape::encode_minimal(input)
             );
 
             // Build a future for sending the response message
             state
-                .telegram_send(msg.text_reply(response).parse_mode(ParseMode::Html))
+                .send(msg.text_reply(response).parse_mode(ParseMode::Html))
                 .map_ok(|_| ())
-                .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                .map_err(|err| Error::Respond(err).into())
                 .await
         } else {
             Ok(())
@@ -91,5 +118,5 @@ impl Action for Duck {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
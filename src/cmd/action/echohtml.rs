@@ -1,13 +1,14 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageKind, ParseMode},
-    Error as TelegramError,
 };
 
 use super::Action;
+use crate::cmd::args::{Arg, Args};
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -19,6 +20,9 @@ const HIDDEN: bool = true;
 /// The action help.
 const HELP: &str = "Echo user input as HTML";
 
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::rest("text", "The HTML to echo")];
+
 pub struct EchoHtml;
 
 impl EchoHtml {
@@ -41,22 +45,20 @@ impl Action for EchoHtml {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
-        if let MessageKind::Text { ref data, .. } = &msg.kind {
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
+        if let MessageKind::Text { .. } = &msg.kind {
             // Get the user's input
-            // TODO: actually properly fetch the user input
-            let input = data
-                .splitn(2, ' ')
-                .nth(1)
-                .map(|cmd| cmd.trim_start())
-                .unwrap_or("")
-                .to_owned();
+            let input = args.get("text").unwrap_or_default().to_owned();
 
             // Build a future for sending the response message
             state
-                .telegram_send(msg.text_reply(input).parse_mode(ParseMode::Html))
+                .send(msg.text_reply(input).parse_mode(ParseMode::Html))
                 .map_ok(|_| ())
-                .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                .map_err(|err| Error::Respond(err).into())
                 .await
         } else {
             Ok(())
@@ -69,5 +71,5 @@ impl Action for EchoHtml {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
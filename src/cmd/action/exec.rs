@@ -3,20 +3,26 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
-use failure::{err_msg, Compat, Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use htmlescape::encode_minimal;
 use humantime::format_duration;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageKind, MessageOrChannelPost, ParseMode},
-    Error as TelegramError,
 };
 use tokio::{pin, time};
 use tokio_stream::wrappers::IntervalStream;
 
+use super::help::build_cmd_help;
 use super::Action;
+use crate::alert::AlertKind;
+use crate::cmd::args::{Arg, Args};
+use crate::cmd::cooldown::Cooldown;
+use crate::cmd::permission::Role;
 use crate::executor::{isolated, Error as ExecutorError};
+use crate::metrics;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -28,11 +34,22 @@ const HIDDEN: bool = false;
 /// The action help.
 const HELP: &str = "Execute a shell command";
 
-/// The number of characters to truncate the output log at.
-const OUTPUT_TRUNCATE: usize = 4096 - 150;
+/// The role required to invoke the action.
+const ROLE: Role = Role::Allowlisted;
 
-/// The timeout duration for commands being executed.
-const EXEC_TIMEOUT: Duration = Duration::from_secs(300);
+/// The action cooldowns.
+const COOLDOWNS: &[Cooldown] = &[Cooldown::per_user(3, 60), Cooldown::global(20, 60)];
+
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::rest("command", "The shell command to run").optional()];
+
+/// The action description.
+const DESCRIPTION: &str = "Runs the command in an isolated container, and keeps updating the \
+                           reply with its output until it completes or times out. The text of \
+                           the replied message is available to the command as $REPLY.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/exec echo Hello!", "/exec uname -a"];
 
 /// The worst precision of the timeout duration for the commands being executed.
 const EXEC_TIMEOUT_PRECISION: Duration = Duration::from_secs(1);
@@ -60,19 +77,36 @@ impl Exec {
         let cmd_text = msg.text();
 
         // Execute the command in an isolated environment, process output and the exit code
+        let name = isolated::container_name();
         let status_output = status.clone();
         let status_exit = status.clone();
-        let cmd = isolated::execute(cmd, reply_text, cmd_text, move |line| {
-            // Append the line to the captured output
-            status_output.lock().unwrap().append_line(&line);
-            Ok(())
-        })
+        let alerts_state = state.clone();
+        let cmd = isolated::execute(
+            state.config(),
+            &name,
+            cmd,
+            reply_text,
+            cmd_text,
+            move |line| {
+                // Append the line to the captured output
+                status_output.lock().unwrap().append_line(&line);
+                Ok(())
+            },
+        )
         .and_then(move |status| {
             // Set the exit status
             status_exit.lock().unwrap().set_status(status);
             future::ok(())
         })
-        .map_err(Error::Execute);
+        .map_err(|err| {
+            // Alert operators if the command could not be spawned at all
+            if let ExecutorError::Spawn(cause) = &err {
+                alerts_state
+                    .alerts()
+                    .record(AlertKind::ExecutorSpawn, cause.to_string());
+            }
+            Error::Execute(err)
+        });
 
         // Set up an interval for constantly updating the status
         let status_update = status.clone();
@@ -84,14 +118,29 @@ impl Exec {
             })
             .map(|_| Ok(()));
 
-        // Run futures
+        // Run futures, until interrupted because the bot is shutting down
         pin!(cmd);
-        future::try_select(status_updater, cmd)
-            .await
-            .map_err(|err| match err {
-                future::Either::Left((e, _)) => e,
-                future::Either::Right((e, _)) => e,
-            })?;
+        let run = future::try_select(status_updater, cmd);
+        let shutdown = state.shutdown().triggered();
+        pin!(shutdown);
+        metrics::EXEC_RUNNING.inc();
+        let result = future::select(run, shutdown).await;
+        metrics::EXEC_RUNNING.dec();
+        match result {
+            future::Either::Left((result, _)) => {
+                result.map_err(|err| match err {
+                    future::Either::Left((e, _)) => e,
+                    future::Either::Right((e, _)) => e,
+                })?;
+            }
+            future::Either::Right(_) => {
+                // Kill the container, and report the command as interrupted
+                if let Err(err) = isolated::kill(&name).await {
+                    error!(%err, "failed to kill interrupted command");
+                }
+                status.lock().unwrap().interrupt();
+            }
+        }
 
         // Update one final time, to ensure all status is sent to Telegram
         status.lock().unwrap().update();
@@ -114,40 +163,47 @@ impl Action for Exec {
         HELP
     }
 
+    fn role(&self) -> Role {
+        ROLE
+    }
+
+    fn cooldowns(&self) -> &'static [Cooldown] {
+        COOLDOWNS
+    }
+
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
     // TODO: proper error handling everywhere, pass errors along
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
-        if let MessageKind::Text { ref data, .. } = &msg.kind {
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
+        if let MessageKind::Text { .. } = &msg.kind {
             // The command to run in the shell
-            // TODO: actually properly fetch the command to execute from the full message
-            let cmd = data
-                .splitn(2, ' ')
-                .nth(1)
-                .map(|cmd| cmd.trim_start())
-                .unwrap_or("")
-                .to_owned();
+            let cmd = args.get("command").unwrap_or_default().to_owned();
 
             // Provide the user with feedback if no command is entered
-            if cmd.trim().is_empty() {
+            if cmd.is_empty() {
                 // Await a future for sending the help message
                 return state
-                    .telegram_send(
-                        msg.text_reply(
-                            "\
-                             Please provide a command to run.\n\
-                             \n\
-                             For example:\n\
-                             `/exec echo Hello!`\
-                             ",
-                        )
-                        .parse_mode(ParseMode::Markdown),
+                    .send(
+                        msg.text_reply(build_cmd_help(&state, self))
+                            .parse_mode(ParseMode::Html),
                     )
                     .map_ok(|_| ())
-                    .map_err(|err| Error::Help(SyncFailure::new(err)).into())
+                    .map_err(|err| Error::Help(err).into())
                     .await;
             }
 
-            // Print the command to run
-            println!("CMD: {}", cmd);
+            // Log the command to run
+            info!(%cmd, "executing command");
 
             // Execute the command, report back to the user
             Self::exec_cmd(state, cmd, &msg)
@@ -177,6 +233,9 @@ pub struct ExecStatus {
     /// The duration it took to complete executing the command.
     completion_duration: Option<Duration>,
 
+    /// True if the command was interrupted before completing, because the bot is shutting down.
+    interrupted: bool,
+
     /// True if the output or status has changed since the last status message update.
     /// If true, this means that the status message doesn't represent the current status corretly,
     /// and thus it should be updated.
@@ -202,24 +261,14 @@ impl ExecStatus {
     /// Create a status output message as reply on the given `msg`,
     /// and return an `ExecStatus` for it.
     pub async fn create_status_msg(state: State, msg: &Message) -> Result<Self, Error> {
-        // TODO: handle the Telegram errors properly
         state
-            .telegram_send(
+            .send(
                 msg.text_reply("<i>Executing command...</i>")
                     .parse_mode(ParseMode::Html),
             )
             .await
-            .map_err(|err| -> FailureError { SyncFailure::new(err).into() })
-            .map_err(|err| Error::StatusMessage(err.compat()))
-            .and_then(|msg| {
-                if let Some(msg) = msg {
-                    Ok(ExecStatus::new(state, msg))
-                } else {
-                    Err(Error::StatusMessage(err_msg(
-                    "failed to send command status message, got empty response from Telegram API",
-                ).compat()))
-                }
-            })
+            .map(|msg| ExecStatus::new(state, msg))
+            .map_err(Error::StatusMessage)
     }
 
     /// Build a new exec status object with the given status message and the global state.
@@ -229,6 +278,7 @@ impl ExecStatus {
             status: None,
             started_at: SystemTime::now(),
             completion_duration: None,
+            interrupted: false,
             changed: false,
             changed_at: SystemTime::now(),
             updated_count: 0,
@@ -246,7 +296,7 @@ impl ExecStatus {
 
         // Truncate the beginning of the output if it became too big
         if self.truncating() {
-            let truncate_at = self.output.len() - OUTPUT_TRUNCATE;
+            let truncate_at = self.output.len() - self.state.config().exec.output_truncate;
             self.output = self.output.split_off(truncate_at);
         }
 
@@ -258,7 +308,7 @@ impl ExecStatus {
 
     /// Check whether the output is being truncated because it became too large.
     fn truncating(&self) -> bool {
-        self.output.len() >= OUTPUT_TRUNCATE
+        self.output.len() >= self.state.config().exec.output_truncate
     }
 
     /// Append the given `line` to the output.
@@ -283,22 +333,36 @@ impl ExecStatus {
         self.completion_duration = self.started_at.elapsed().ok();
     }
 
+    /// Mark the command as interrupted, because the bot is shutting down.
+    pub fn interrupt(&mut self) {
+        self.interrupted = true;
+        self.changed = true;
+        self.completion_duration = self.started_at.elapsed().ok();
+    }
+
     /// Check whether this executable has completed.
     /// It may have successfully completed or it may have failed.
     pub fn completed(&self) -> bool {
         self.status.is_some()
     }
 
+    /// Check whether this executable has finished, either by completing or by being interrupted.
+    pub fn finished(&self) -> bool {
+        self.completed() || self.interrupted
+    }
+
     /// Build the status message contents, based on the current executing status.
     /// The returned status message is in HTML format.
     fn build_status_msg(&self) -> String {
-        // If not completed, and there is no output yet
-        if !self.completed() && self.output.is_empty() {
+        // If not finished, and there is no output yet
+        if !self.finished() && self.output.is_empty() {
             return "<i>Executing command...</i>".into();
         }
 
         // Determine what status emoji to use
-        let emoji = if !self.completed() {
+        let emoji = if self.interrupted {
+            "⛔"
+        } else if !self.completed() {
             "⏳"
         } else if self.status.unwrap().success() {
             "✅"
@@ -320,20 +384,23 @@ impl ExecStatus {
 
         // Add some additional status labels to the notice if relevant
         let mut status_labels = Vec::new();
-        if !self.completed() && self.throttling(1) {
+        if !self.finished() && self.throttling(1) {
             status_labels.push(format!("throttling {}s", self.throttle_secs(1)));
         }
         if self.timed_out() {
             status_labels.push("timed out".into());
         }
-        if self.completed() && self.completion_duration.is_some() {
+        if self.interrupted {
+            status_labels.push("interrupted, bot is shutting down".into());
+        }
+        if self.finished() && self.completion_duration.is_some() {
             status_labels.push(format!(
                 "took {}",
                 self.format_duration().unwrap_or_else(|| "?".into())
             ));
         }
         if self.truncating() {
-            if self.completed() {
+            if self.finished() {
                 status_labels.push("truncated".into());
             } else {
                 status_labels.push("truncating".into());
@@ -377,7 +444,7 @@ impl ExecStatus {
     // TODO: should we return a future for updating, to allow catching errors?
     pub fn update_status_msg(&mut self) {
         // Spawn a future to edit the status message with the newest build status text
-        self.state.telegram_spawn(
+        self.state.spawn_send(
             self.status_msg
                 .edit_text(self.build_status_msg())
                 .parse_mode(ParseMode::Html)
@@ -432,25 +499,16 @@ impl ExecStatus {
     /// An update count offset may be given.
     fn throttle_secs(&self, offset: i64) -> u64 {
         // Get the update count
-        let count = self.updated_count as i64 + offset;
-
-        // TODO: make the throttle time configurable
-        if count < 2 {
-            1
-        } else if count < 5 {
-            3
-        } else if count < 8 {
-            5
-        } else {
-            10
-        }
+        let count = (self.updated_count as i64 + offset).max(0) as usize;
+
+        self.state.config().exec.throttle_secs(count)
     }
 
     /// The time to wait while throttling before sending the next update to Telegram.
     /// The throttle time gradually increases the more messages updates are sent, to prevent
     /// hitting the rate limit enforced by Telegram for sending message updates.
     fn throttle_duration(&self) -> Duration {
-        Duration::from_secs(self.throttle_secs(0)) - Duration::from_millis(50)
+        Duration::from_secs(self.throttle_secs(0)).saturating_sub(Duration::from_millis(50))
     }
 
     /// Check if the user command timed out.
@@ -463,8 +521,9 @@ impl ExecStatus {
         }
 
         // If a duration is known, it must reach the timeout time
+        let timeout = self.state.config().exec.timeout();
         match self.completion_duration {
-            Some(duration) if duration >= EXEC_TIMEOUT - EXEC_TIMEOUT_PRECISION => {}
+            Some(duration) if duration >= timeout.saturating_sub(EXEC_TIMEOUT_PRECISION) => {}
             Some(_) => return false,
             _ => {}
         }
@@ -499,11 +558,11 @@ pub enum Error {
     /// An error occurred while sending the help message which is sent when no command input is
     /// given.
     #[fail(display = "failed to send help response message")]
-    Help(#[cause] SyncFailure<TelegramError>),
+    Help(#[cause] SendError),
 
     /// Failed to send the initial status message to update later on as the process continues.
     #[fail(display = "failed to send command status message")]
-    StatusMessage(#[cause] Compat<FailureError>),
+    StatusMessage(#[cause] SendError),
 
     /// An error occurred while executing the user command.
     #[fail(display = "failed to execute user shell command")]
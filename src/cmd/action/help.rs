@@ -1,13 +1,18 @@
+use std::iter;
+
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
+use htmlescape::encode_minimal;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
 use super::{Action, ACTIONS};
+use crate::cmd::args::{usage, Arg, Args};
+use crate::cmd::permission::Role;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -19,6 +24,16 @@ const HIDDEN: bool = false;
 /// The action help.
 const HELP: &str = "Show help";
 
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::positional("command", "The command to show details for").optional()];
+
+/// The action description.
+const DESCRIPTION: &str = "Lists all commands, or shows the usage, arguments and examples of the \
+                           given command.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/help", "/help duck"];
+
 pub struct Help;
 
 impl Help {
@@ -41,18 +56,54 @@ impl Action for Help {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
+        // Show the detailed help of a single command if given
+        if let Some(cmd) = args.get("command") {
+            let cmd = cmd.trim_start_matches('/');
+            let response = match ACTIONS
+                .iter()
+                .find(|action| !action.hidden() && action.is_cmd(cmd))
+            {
+                Some(action) => build_cmd_help(&state, action.as_ref()),
+                None => format!(
+                    "There is no command /{}, send /{} to list all commands.",
+                    encode_minimal(cmd),
+                    CMD,
+                ),
+            };
+            return state
+                .send(msg.text_reply(response).parse_mode(ParseMode::Html))
+                .map_ok(|_| ())
+                .map_err(|err| Error::Respond(err).into())
+                .await;
+        }
+
         // Build the command list
         let cmd_list = build_help_list();
 
         // Build a future for sending the response help message
         state
-            .telegram_send(
-                msg.text_reply(format!("*RISC commands:*\n{}", cmd_list,))
-                    .parse_mode(ParseMode::Markdown),
+            .send(
+                msg.text_reply(format!(
+                    "*RISC commands:*\n{}\n\nSend `/{} <command>` for details.",
+                    cmd_list, CMD,
+                ))
+                .parse_mode(ParseMode::Markdown),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -62,16 +113,145 @@ pub(crate) fn build_help_list() -> String {
     let mut cmds: Vec<String> = ACTIONS
         .iter()
         .filter(|action| !action.hidden())
-        .map(|action| format!("/{}: _{}_", action.cmd(), action.help(),))
+        .map(|action| {
+            let names: Vec<String> = iter::once(action.cmd())
+                .chain(action.aliases().iter().copied())
+                .map(|name| format!("/{}", name))
+                .collect();
+            format!("{}: _{}_", names.join(", "), action.help())
+        })
         .collect();
     cmds.sort();
     cmds.join("\n")
 }
 
+/// Build the detailed help of the given action, with its usage, arguments and examples.
+///
+/// The returned help is in HTML format.
+pub(crate) fn build_cmd_help(state: &State, action: &dyn Action) -> String {
+    // Build the header, add the aliases
+    let mut help = format!(
+        "<b>/{}</b>: {}",
+        action.cmd(),
+        encode_minimal(action.help())
+    );
+    if !action.aliases().is_empty() {
+        let aliases: Vec<String> = action
+            .aliases()
+            .iter()
+            .map(|alias| format!("/{}", alias))
+            .collect();
+        help += &format!("\nAliases: {}", aliases.join(", "));
+    }
+
+    // Append the description
+    if let Some(description) = action.description() {
+        help += &format!("\n\n{}", encode_minimal(description));
+    }
+
+    // Append the usage, and describe each argument
+    help += &format!(
+        "\n\nUsage: <code>{}</code>",
+        encode_minimal(&usage(action.cmd(), action.args())),
+    );
+    for arg in action.args() {
+        help += &format!(
+            "\n<code>{}</code>: {}",
+            encode_minimal(&arg.usage()),
+            encode_minimal(arg.help),
+        );
+    }
+
+    // Append the examples
+    if !action.examples().is_empty() {
+        help += "\n\n<b>Examples:</b>";
+        for example in action.examples() {
+            help += &format!("\n<code>{}</code>", encode_minimal(example));
+        }
+    }
+
+    // Note who may use the action
+    let role = state.config().bot.role(action.cmd(), action.role());
+    if role != Role::Anyone {
+        help += &format!("\n\nOnly available to {}.", role);
+    }
+
+    help
+}
+
 /// A help action error.
 #[derive(Debug, Fail)]
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::permission::Role;
+    use crate::config::Config;
+    use crate::testing::{self, Harness, GROUP_ID};
+
+    /// Handle the given help command, and get the text of the reply.
+    async fn reply(harness: &Harness, cmd: &str) -> String {
+        harness.transport.clear();
+        harness
+            .handle(testing::message(GROUP_ID, cmd))
+            .await
+            .unwrap();
+        let texts = harness.sent_texts();
+        assert_eq!(texts.len(), 1);
+        texts[0].clone()
+    }
+
+    #[tokio::test]
+    async fn lists_visible_commands() {
+        let harness = Harness::new().await;
+        let help = reply(&harness, "/help").await;
+        assert!(help.starts_with("*RISC commands:*\n"), "{}", help);
+        assert!(help.contains("\n/duck, /search, /ddg: _"), "{}", help);
+        assert!(help.contains("\n/help: _Show help_"), "{}", help);
+        assert!(!help.contains("/echo"), "{}", help);
+    }
+
+    #[tokio::test]
+    async fn shows_command_details() {
+        let harness = Harness::new().await;
+        let help = reply(&harness, "/help duck").await;
+        assert!(help.starts_with("<b>/duck</b>: "), "{}", help);
+        assert!(help.contains("\nAliases: /search, /ddg\n"), "{}", help);
+        assert!(
+            help.contains("\n\nUsage: <code>/duck [&lt;query...&gt;]</code>\n"),
+            "{}",
+            help
+        );
+        assert!(help.contains("\n\n<b>Examples:</b>\n"), "{}", help);
+        assert!(!help.contains("Only available to"), "{}", help);
+
+        // Commands are found by their alias, with or without slash
+        assert_eq!(reply(&harness, "/help /ddg").await, help);
+    }
+
+    #[tokio::test]
+    async fn hides_hidden_commands() {
+        let harness = Harness::new().await;
+        assert_eq!(
+            reply(&harness, "/help echo").await,
+            "There is no command /echo, send /help to list all commands."
+        );
+    }
+
+    #[tokio::test]
+    async fn notes_required_role() {
+        let mut config = Config::default();
+        config.bot.roles.insert("exec".into(), Role::Allowlisted);
+        let harness = Harness::with_config(config).await;
+        assert!(reply(&harness, "/help settings")
+            .await
+            .ends_with("\n\nOnly available to chat admins."));
+        assert!(reply(&harness, "/help exec")
+            .await
+            .ends_with("\n\nOnly available to allowlisted users."));
+    }
 }
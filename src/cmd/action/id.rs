@@ -2,8 +2,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use chrono::{DateTime, NaiveDateTime, Utc};
-use failure::{err_msg, Compat, Error as FailureError, SyncFailure};
-use futures::future;
+use failure::Error as FailureError;
 use futures::prelude::*;
 use humansize::{file_size_opts, FileSize};
 use humantime::format_duration;
@@ -13,10 +12,11 @@ use telegram_bot::{
         ChannelPost, ForwardFrom, Message, MessageChat, MessageKind, MessageOrChannelPost,
         ParseMode, User,
     },
-    Error as TelegramError,
 };
 
 use super::Action;
+use crate::cmd::args::{Arg, Args};
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -28,6 +28,16 @@ const HIDDEN: bool = false;
 /// The action help.
 const HELP: &str = "Show hidden message/chat details";
 
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::reply("message", "The message to show details for").optional()];
+
+/// The action description.
+const DESCRIPTION: &str = "Shows the IDs and other details of this chat, of you, and of the \
+                           replied message and its sender.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/id"];
+
 pub struct Id;
 
 impl Id {
@@ -704,30 +714,31 @@ impl Action for Id {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
         // Own the message and global state
         let msg = msg.clone();
         let state = state.clone();
 
         // Build a future to send a temporary response to claim an ID for the answer message
         let response = state
-            .telegram_send(
+            .send(
                 msg.text_reply("_Gathering facts..._")
                     .parse_mode(ParseMode::Markdown)
                     .to_owned(),
             )
-            .map_err(|err| -> FailureError { SyncFailure::new(err).into() })
-            .map_err(|err| Error::GatherFacts(err.compat()))
-            .and_then(|msg_answer| {
-                if let Some(msg_answer) = msg_answer {
-                    future::ok(msg_answer)
-                } else {
-                    future::err(Error::GatherFacts(
-                        err_msg("failed to gather facts, got empty response from Telegram API")
-                            .compat(),
-                    ))
-                }
-            });
+            .map_err(Error::GatherFacts);
 
         // Build the ID details message and update the answer
         response
@@ -741,7 +752,7 @@ impl Action for Id {
                 info.push(Self::build_msg_info(&msg, "Your message"));
 
                 // Information about a quoted message by the sender
-                if let Some(ref reply_to) = msg.reply_to_message {
+                if let Some(reply_to) = args.reply() {
                     info.push(Self::build_msg_channel_post_info(
                         reply_to,
                         "Your quoted message",
@@ -765,7 +776,7 @@ impl Action for Id {
                 }
 
                 // Tell a user he may reply to an existing message
-                if msg.reply_to_message.is_none() {
+                if args.reply().is_none() {
                     info.push(String::from(
                         "_Note: reply to an existing message with /id to show it's details._",
                     ));
@@ -773,7 +784,7 @@ impl Action for Id {
 
                 // Build a future to update the temporary message with the actual ID response
                 state
-                    .telegram_send_message(
+                    .send(
                         msg_answer
                             .edit_text(info.join("\n\n"))
                             .parse_mode(ParseMode::Markdown)
@@ -781,7 +792,7 @@ impl Action for Id {
                             .to_owned(),
                     )
                     .map_ok(|_| ())
-                    .map_err(|err| Error::Respond(SyncFailure::new(err)))
+                    .map_err(Error::Respond)
             })
             .map_err(|err| err.into())
             .await
@@ -793,10 +804,10 @@ impl Action for Id {
 pub enum Error {
     /// An error occurred while sending the first temporary response to gather facts.
     #[fail(display = "failed to send temporary response to gather ID facts")]
-    GatherFacts(#[cause] Compat<FailureError>),
+    GatherFacts(#[cause] SendError),
 
     /// An error occurred while sending the actual response by updating the temporary response
     /// message that was used for gathering facts.
     #[fail(display = "failed to update temporary response with actual ID details")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
@@ -1,3 +1,4 @@
+pub mod alias;
 pub mod all;
 pub mod duck;
 pub mod echo;
@@ -8,6 +9,7 @@ pub mod id;
 pub mod ping;
 pub mod retweet;
 pub mod risc;
+pub mod settings;
 pub mod start;
 pub mod stats;
 pub mod test;
@@ -16,12 +18,17 @@ use async_trait::async_trait;
 use failure::{Compat, Error as FailureError};
 use telegram_bot::types::Message;
 
+use crate::cmd::alias::normalize;
+use crate::cmd::args::{Arg, Args};
+use crate::cmd::cooldown::Cooldown;
+use crate::cmd::permission::Role;
 use crate::state::State;
 
 lazy_static! {
     /// A list of all available and invokable actions.
     /// This list includes hidden actions which may be filtered using the `.hidden()` propery.
     pub(crate) static ref ACTIONS: Vec<Box<dyn Action + Sync>> = vec![
+        Box::new(self::alias::Alias::new()),
         Box::new(self::all::All::new()),
         Box::new(self::duck::Duck::new()),
         Box::new(self::echo::Echo::new()),
@@ -32,6 +39,7 @@ lazy_static! {
         Box::new(self::ping::Ping::new()),
         Box::new(self::retweet::Retweet::new()),
         Box::new(self::risc::Risc::new()),
+        Box::new(self::settings::Settings::new()),
         Box::new(self::start::Start::new()),
         Box::new(self::stats::Stats::new()),
         Box::new(self::test::Test::new()),
@@ -46,11 +54,19 @@ pub trait Action {
     /// The returned value should be in lowercase and must not contain any whitespace.
     fn cmd(&self) -> &'static str;
 
-    /// Check whether this action is for the command with the given name.
+    /// Get alternative command names for this action.
     ///
-    /// The name of the command `/ping` would be `ping`.
+    /// Like the command name, aliases should be in lowercase and must not contain whitespace.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Check whether this action is for the command with the given name or alias.
+    ///
+    /// The name of the command `/ping` would be `ping`. Names are compared regardless of case.
     fn is_cmd(&self, cmd: &str) -> bool {
-        cmd.trim().eq_ignore_ascii_case(self.cmd())
+        let cmd = normalize(cmd);
+        cmd == self.cmd() || self.aliases().contains(&cmd.as_str())
     }
 
     /// Whether this command is hidden from `/help` output and such.
@@ -59,8 +75,40 @@ pub trait Action {
     /// Short help information for this action.
     fn help(&self) -> &'static str;
 
-    /// Invoke the action with the given context.
-    async fn invoke(&self, state: State, sg: Message) -> Result<(), FailureError>;
+    /// The arguments this action accepts.
+    ///
+    /// Arguments given to the command are parsed and validated against this schema before the
+    /// action is invoked. Commands with invalid arguments are answered with usage information.
+    /// Actions without arguments ignore any input given to the command.
+    fn args(&self) -> &'static [Arg] {
+        &[]
+    }
+
+    /// Long-form usage information for this action, shown along with its arguments in the detailed
+    /// help of the action.
+    fn description(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Example invocations of this action, shown in the detailed help of the action.
+    fn examples(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The role a user must have to invoke this action.
+    fn role(&self) -> Role {
+        Role::Anyone
+    }
+
+    /// The cooldown policies limiting how often this action may be invoked.
+    ///
+    /// Invocations exceeding any of the policies are refused. Bot owners are not limited.
+    fn cooldowns(&self) -> &'static [Cooldown] {
+        &[]
+    }
+
+    /// Invoke the action with the given context and parsed arguments.
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError>;
 }
 
 /// An action error.
@@ -77,4 +125,14 @@ pub enum Error {
         /// The name of the action.
         name: String,
     },
+
+    /// An action panicked while being invoked.
+    #[fail(display = "action panicked: {}", message)]
+    Panic {
+        /// The panic message.
+        message: String,
+
+        /// The name of the action.
+        name: String,
+    },
 }
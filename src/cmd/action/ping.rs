@@ -1,9 +1,11 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
-use telegram_bot::{prelude::*, types::Message, Error as TelegramError};
+use telegram_bot::{prelude::*, types::Message};
 
 use super::Action;
+use crate::cmd::args::Args;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -37,12 +39,12 @@ impl Action for Ping {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Build a message future for sending the response
         state
-            .telegram_send(msg.text_reply("Pong!"))
+            .send(msg.text_reply("Pong!"))
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -52,5 +54,5 @@ impl Action for Ping {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
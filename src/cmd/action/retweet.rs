@@ -1,25 +1,40 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use regex::Regex;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageKind, MessageOrChannelPost, ParseMode},
-    Error as TelegramError,
 };
 
+use super::help::build_cmd_help;
 use super::Action;
+use crate::cmd::args::{Arg, Args};
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
 const CMD: &str = "rt";
 
+/// The action command aliases.
+const ALIASES: &[&str] = &["retweet"];
+
 /// Whether the action is hidden.
 const HIDDEN: bool = false;
 
 /// The action help.
 const HELP: &str = "Retweet a message";
 
+/// The action arguments.
+const ARGS: &[Arg] = &[Arg::reply("message", "The message to retweet").optional()];
+
+/// The action description.
+const DESCRIPTION: &str =
+    "Reply to a text message with this command to retweet it in the chat, under your name.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/rt"];
+
 lazy_static! {
     /// A regex for matching a retweeted message
     static ref RT_REGEX: Regex = Regex::new(
@@ -41,6 +56,10 @@ impl Action for Retweet {
         CMD
     }
 
+    fn aliases(&self) -> &'static [&'static str] {
+        ALIASES
+    }
+
     fn hidden(&self) -> bool {
         HIDDEN
     }
@@ -49,35 +68,42 @@ impl Action for Retweet {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    fn args(&self) -> &'static [Arg] {
+        ARGS
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
+    async fn invoke(&self, state: State, msg: Message, args: Args) -> Result<(), FailureError> {
         // Get the reply message which we should retweet
-        let retweet_msg: &Message = match &msg.reply_to_message {
-            Some(ref msg) => match msg.as_ref() {
+        let retweet_msg: &Message = match args.reply() {
+            Some(reply) => match reply {
                 MessageOrChannelPost::Message(msg) => msg,
                 MessageOrChannelPost::ChannelPost(_) => {
                     return state
-                        .telegram_send(
+                        .send(
                             msg.text_reply("You can't retweet a channel post.")
                                 .parse_mode(ParseMode::Markdown),
                         )
                         .map_ok(|_| ())
-                        .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                        .map_err(|err| Error::Respond(err).into())
                         .await
                 }
             },
             None => {
                 return state
-                    .telegram_send(
-                        msg.text_reply(format!(
-                            "\
-                                 To retweet, you must reply to a message with the `/{}` command.\
-                                 ",
-                            CMD,
-                        ))
-                        .parse_mode(ParseMode::Markdown),
+                    .send(
+                        msg.text_reply(build_cmd_help(&state, self))
+                            .parse_mode(ParseMode::Html),
                     )
                     .map_ok(|_| ())
-                    .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                    .map_err(|err| Error::Respond(err).into())
                     .await
             }
         };
@@ -104,7 +130,7 @@ impl Action for Retweet {
 
                 // Send the retweet message
                 state
-                    .telegram_send(
+                    .send(
                         retweet_msg
                             .text_reply(format!(
                                 "\
@@ -114,17 +140,17 @@ impl Action for Retweet {
                             .parse_mode(ParseMode::Html),
                     )
                     .map_ok(|_| ())
-                    .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                    .map_err(|err| Error::Respond(err).into())
                     .await
             }
             _ => {
                 state
-                    .telegram_send(
+                    .send(
                         msg.text_reply("Only text messages can be retweeted at this moment.")
                             .parse_mode(ParseMode::Markdown),
                     )
                     .map_ok(|_| ())
-                    .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+                    .map_err(|err| Error::Respond(err).into())
                     .await
             }
         }
@@ -136,5 +162,5 @@ impl Action for Retweet {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
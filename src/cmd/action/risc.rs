@@ -1,14 +1,15 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
 use super::Action;
 use crate::app::{NAME, VERSION};
+use crate::cmd::args::Args;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -42,10 +43,10 @@ impl Action for Risc {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Build a future for sending the response message
         state
-            .telegram_send(
+            .send(
                 msg.text_reply(format!(
                     "\
                      `{} v{}`\n\
@@ -61,7 +62,7 @@ impl Action for Risc {
                 .parse_mode(ParseMode::Markdown),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -71,5 +72,5 @@ impl Action for Risc {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
@@ -0,0 +1,208 @@
+use std::mem;
+
+use async_trait::async_trait;
+use failure::{Error as FailureError, SyncFailure};
+use futures::prelude::*;
+use telegram_bot::{
+    prelude::*,
+    types::{
+        CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageOrChannelPost,
+    },
+    Error as TelegramError,
+};
+
+use super::Action;
+use crate::cmd::args::Args;
+use crate::cmd::permission::{Error as PermissionError, Role};
+use crate::db::QueryError;
+use crate::send::Error as SendError;
+use crate::settings::{ChatSettings, Feature};
+use crate::state::State;
+use crate::stats::TelegramToI64;
+
+/// The action command name.
+const CMD: &str = "settings";
+
+/// Whether the action is hidden.
+const HIDDEN: bool = false;
+
+/// The action help.
+const HELP: &str = "Enable or disable features in this chat";
+
+/// The role required to invoke the action.
+const ROLE: Role = Role::ChatAdmin;
+
+/// The action description.
+const DESCRIPTION: &str = "Shows a keyboard with the features of this chat, tap one to enable or \
+                           disable it. The /settings command itself can't be disabled.";
+
+/// The action examples.
+const EXAMPLES: &[&str] = &["/settings"];
+
+/// The prefix of the callback data of the settings keyboard buttons.
+const CALLBACK_PREFIX: &str = "settings:";
+
+/// The number of buttons on each row of the settings keyboard.
+const BUTTONS_PER_ROW: usize = 2;
+
+pub struct Settings;
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings
+    }
+}
+
+#[async_trait]
+impl Action for Settings {
+    fn cmd(&self) -> &'static str {
+        CMD
+    }
+
+    fn hidden(&self) -> bool {
+        HIDDEN
+    }
+
+    fn help(&self) -> &'static str {
+        HELP
+    }
+
+    fn role(&self) -> Role {
+        ROLE
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some(DESCRIPTION)
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        EXAMPLES
+    }
+
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
+        let settings = state
+            .settings()
+            .chat(&state, msg.chat.id().to_i64())
+            .await
+            .map_err(Error::Settings)?;
+
+        // Build a future for sending the settings keyboard
+        state
+            .send(
+                msg.text_reply("Settings for this chat, tap a feature to toggle it:")
+                    .reply_markup(build_keyboard(&settings)),
+            )
+            .map_ok(|_| ())
+            .map_err(|err| Error::Respond(err).into())
+            .await
+    }
+}
+
+/// Build the settings keyboard, with a toggle button for each feature.
+fn build_keyboard(settings: &ChatSettings) -> InlineKeyboardMarkup {
+    let mut keyboard = InlineKeyboardMarkup::new();
+    let mut row = Vec::new();
+    for feature in Feature::all() {
+        let icon = if settings.is_enabled(feature) {
+            "✅"
+        } else {
+            "❌"
+        };
+        row.push(InlineKeyboardButton::callback(
+            format!("{} {}", icon, feature.label()),
+            format!("{}{}", CALLBACK_PREFIX, feature.key()),
+        ));
+        if row.len() == BUTTONS_PER_ROW {
+            keyboard.add_row(mem::take(&mut row));
+        }
+    }
+    if !row.is_empty() {
+        keyboard.add_row(row);
+    }
+    keyboard
+}
+
+/// Handle a callback query from the settings keyboard, by toggling the chosen feature.
+///
+/// Callback queries not coming from the settings keyboard are ignored.
+pub async fn handle_callback(state: State, query: CallbackQuery) -> Result<(), Error> {
+    let key = match query
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(CALLBACK_PREFIX))
+    {
+        Some(key) => key.to_owned(),
+        None => return Ok(()),
+    };
+    let msg = match &query.message {
+        Some(MessageOrChannelPost::Message(msg)) => msg.clone(),
+        _ => return answer(&state, &query, "These settings can't be changed here.").await,
+    };
+
+    // Only allow chat admins to change settings
+    let allowed = state
+        .permissions()
+        .allows(
+            &state,
+            state.config().bot.role(CMD, ROLE),
+            &msg.chat,
+            query.from.id.to_i64(),
+        )
+        .await
+        .map_err(Error::Permission)?;
+    if !allowed {
+        return answer(&state, &query, "Only chat admins may change settings.").await;
+    }
+
+    // Toggle the feature, and update the keyboard
+    let feature = match Feature::from_key(&key) {
+        Some(feature) => feature,
+        None => return answer(&state, &query, "This feature no longer exists.").await,
+    };
+    let settings = state
+        .settings()
+        .toggle(&state, msg.chat.id().to_i64(), feature)
+        .await
+        .map_err(Error::Settings)?;
+    state
+        .send(msg.edit_reply_markup(Some(build_keyboard(&settings))))
+        .await
+        .map_err(Error::Respond)?;
+
+    let status = if settings.is_enabled(feature) {
+        "enabled"
+    } else {
+        "disabled"
+    };
+    answer(&state, &query, &format!("{} {}", feature.label(), status)).await
+}
+
+/// Answer the given callback query with a short notification.
+async fn answer(state: &State, query: &CallbackQuery, text: &str) -> Result<(), Error> {
+    state
+        .telegram_client()
+        .send(query.answer(text))
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::Answer(SyncFailure::new(err)))
+}
+
+/// A settings action error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// An error occurred while loading or changing the chat settings.
+    #[fail(display = "failed to load or change chat settings")]
+    Settings(#[cause] QueryError),
+
+    /// Failed to check whether the user may change the settings.
+    #[fail(display = "failed to check settings permissions")]
+    Permission(#[cause] PermissionError),
+
+    /// An error occurred while sending a response message to the user.
+    #[fail(display = "failed to send response message")]
+    Respond(#[cause] SendError),
+
+    /// An error occurred while answering a callback query of the settings keyboard.
+    #[fail(display = "failed to answer settings callback query")]
+    Answer(#[cause] SyncFailure<TelegramError>),
+}
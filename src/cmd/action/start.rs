@@ -1,15 +1,17 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageChat, MessageKind, ParseMode},
-    Error as TelegramError,
 };
 
 use super::help::build_help_list;
 use super::Action;
+use crate::cmd::args::Args;
+use crate::send::Error as SendError;
 use crate::state::State;
+use crate::util::escape_markdown;
 
 /// The action command name.
 const CMD: &str = "start";
@@ -42,7 +44,7 @@ impl Action for Start {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Do not respond in non-private chats
         if let MessageKind::Text { .. } = &msg.kind {
             match &msg.chat {
@@ -53,26 +55,27 @@ impl Action for Start {
 
         // Build a future for sending the response start message
         state
-            .telegram_send(
+            .send(
                 msg.text_reply(format!(
                     "\
                             *Welcome {}!*\n\
                             \n\
                             This bot adds useful features to Telegram such as message stats \
                             tracking, and is intended to be used in group chats. \
-                            Add @riscbot to a group chat to start using it.\n\
+                            Add @{} to a group chat to start using it.\n\
                             \n\
                             You may choose one of the following commands to try it out:\n\
                             \n\
                             {}
                         ",
-                    msg.from.first_name,
+                    escape_markdown(&msg.from.first_name),
+                    escape_markdown(&state.bot().username),
                     build_help_list(),
                 ))
                 .parse_mode(ParseMode::Markdown),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -82,5 +85,5 @@ impl Action for Start {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
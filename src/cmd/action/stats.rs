@@ -1,14 +1,16 @@
 use async_trait::async_trait;
 use diesel::result::Error as DieselError;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
 use super::Action;
+use crate::cmd::args::Args;
+use crate::cmd::cooldown::Cooldown;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -20,6 +22,9 @@ const HIDDEN: bool = false;
 /// The action help.
 const HELP: &str = "Display message stats";
 
+/// The action cooldowns.
+const COOLDOWNS: &[Cooldown] = &[Cooldown::per_chat(2, 60)];
+
 pub struct Stats;
 
 impl Stats {
@@ -42,7 +47,11 @@ impl Action for Stats {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    fn cooldowns(&self) -> &'static [Cooldown] {
+        COOLDOWNS
+    }
+
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Fetch the chat message stats
         let stats = match state.stats().fetch_chat_stats(
             state.db_connection(),
@@ -108,13 +117,13 @@ impl Action for Stats {
 
         // Build a message future for sending the response
         state
-            .telegram_send(
+            .send(
                 msg.text_reply(response)
                     .parse_mode(ParseMode::Markdown)
                     .disable_preview(),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -128,7 +137,7 @@ pub enum Error {
 
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
 
 impl From<DieselError> for Error {
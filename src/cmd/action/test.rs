@@ -1,13 +1,14 @@
 use async_trait::async_trait;
-use failure::{Error as FailureError, SyncFailure};
+use failure::Error as FailureError;
 use futures::prelude::*;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
 use super::Action;
+use crate::cmd::args::Args;
+use crate::send::Error as SendError;
 use crate::state::State;
 
 /// The action command name.
@@ -41,15 +42,15 @@ impl Action for Test {
         HELP
     }
 
-    async fn invoke(&self, state: State, msg: Message) -> Result<(), FailureError> {
+    async fn invoke(&self, state: State, msg: Message, _args: Args) -> Result<(), FailureError> {
         // Build a future for sending the response message
         state
-            .telegram_send(
+            .send(
                 msg.text_reply("<i>Jep... works on my machine!</i>")
                     .parse_mode(ParseMode::Html),
             )
             .map_ok(|_| ())
-            .map_err(|err| Error::Respond(SyncFailure::new(err)).into())
+            .map_err(|err| Error::Respond(err).into())
             .await
     }
 }
@@ -59,5 +60,5 @@ impl Action for Test {
 pub enum Error {
     /// An error occurred while sending a response message to the user.
     #[fail(display = "failed to send response message")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
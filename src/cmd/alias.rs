@@ -0,0 +1,92 @@
+use diesel::prelude::*;
+
+use crate::db::QueryError;
+use crate::schema::chat_alias;
+use crate::state::State;
+
+/// The maximum length of a custom alias.
+pub const MAX_LENGTH: usize = 32;
+
+/// Normalize the given command or alias name, to look it up regardless of its case.
+pub fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Check whether the given name is valid as custom alias.
+///
+/// Like command names, aliases may only contain letters, digits and underscores.
+pub fn is_valid(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias.chars().count() <= MAX_LENGTH
+        && alias.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Get the command the given custom alias of a chat refers to, if it exists.
+pub async fn resolve(state: &State, chat: i64, alias: &str) -> Result<Option<String>, QueryError> {
+    let alias = normalize(alias);
+    state
+        .db_query(move |connection| {
+            with_connection!(connection, |conn| chat_alias::table
+                .filter(chat_alias::chat_id.eq(chat))
+                .filter(chat_alias::alias.eq(&alias))
+                .select(chat_alias::command)
+                .first::<String>(conn)
+                .optional())
+        })
+        .await
+}
+
+/// List all custom aliases of a chat along with their command, ordered by alias.
+pub async fn list(state: &State, chat: i64) -> Result<Vec<(String, String)>, QueryError> {
+    state
+        .db_query(move |connection| {
+            with_connection!(connection, |conn| chat_alias::table
+                .filter(chat_alias::chat_id.eq(chat))
+                .select((chat_alias::alias, chat_alias::command))
+                .order(chat_alias::alias)
+                .load::<(String, String)>(conn))
+        })
+        .await
+}
+
+/// Set a custom alias for a chat, replacing any existing alias with the same name.
+pub async fn set(state: &State, chat: i64, alias: &str, command: &str) -> Result<(), QueryError> {
+    let alias = normalize(alias);
+    let command = normalize(command);
+    state
+        .db_query(move |connection| {
+            with_connection!(connection, |conn| conn.transaction(|| {
+                diesel::delete(
+                    chat_alias::table
+                        .filter(chat_alias::chat_id.eq(chat))
+                        .filter(chat_alias::alias.eq(&alias)),
+                )
+                .execute(conn)?;
+                diesel::insert_into(chat_alias::table)
+                    .values((
+                        chat_alias::chat_id.eq(chat),
+                        chat_alias::alias.eq(&alias),
+                        chat_alias::command.eq(&command),
+                    ))
+                    .execute(conn)
+            }))
+            .map(|_| ())
+        })
+        .await
+}
+
+/// Remove a custom alias of a chat, returns `false` if it did not exist.
+pub async fn remove(state: &State, chat: i64, alias: &str) -> Result<bool, QueryError> {
+    let alias = normalize(alias);
+    state
+        .db_query(move |connection| {
+            with_connection!(connection, |conn| diesel::delete(
+                chat_alias::table
+                    .filter(chat_alias::chat_id.eq(chat))
+                    .filter(chat_alias::alias.eq(&alias)),
+            )
+            .execute(conn))
+            .map(|deleted| deleted > 0)
+        })
+        .await
+}
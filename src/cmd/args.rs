@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+
+use telegram_bot::types::{Message, MessageOrChannelPost};
+
+/// The kind of an argument an action accepts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A single word, or a string in single or double quotes.
+    Positional,
+
+    /// A flag such as `--verbose`, which is either given or not.
+    Flag,
+
+    /// All remaining input as-is, including whitespace and newlines.
+    Rest,
+
+    /// The message the command replies to.
+    Reply,
+}
+
+/// An argument an action accepts, as part of its argument schema.
+#[derive(Copy, Clone, Debug)]
+pub struct Arg {
+    /// The name of the argument, used to obtain its value.
+    pub name: &'static str,
+
+    /// The kind of argument.
+    pub kind: ArgKind,
+
+    /// Whether the argument must be given. Flags are never required.
+    pub required: bool,
+
+    /// Short help information for this argument.
+    pub help: &'static str,
+}
+
+impl Arg {
+    /// A required positional argument.
+    pub const fn positional(name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name,
+            kind: ArgKind::Positional,
+            required: true,
+            help,
+        }
+    }
+
+    /// A flag, given as `--name`.
+    pub const fn flag(name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name,
+            kind: ArgKind::Flag,
+            required: false,
+            help,
+        }
+    }
+
+    /// A required argument taking all remaining input.
+    ///
+    /// This must be the last argument besides flags and the reply target.
+    pub const fn rest(name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name,
+            kind: ArgKind::Rest,
+            required: true,
+            help,
+        }
+    }
+
+    /// A required reply target, being the message the command replies to.
+    pub const fn reply(name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name,
+            kind: ArgKind::Reply,
+            required: true,
+            help,
+        }
+    }
+
+    /// Make this argument optional.
+    pub const fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    /// Format this argument for usage information, such as `<query>` or `[--verbose]`.
+    pub fn usage(&self) -> String {
+        let usage = match self.kind {
+            ArgKind::Positional => format!("<{}>", self.name),
+            ArgKind::Flag => format!("--{}", self.name),
+            ArgKind::Rest => format!("<{}...>", self.name),
+            ArgKind::Reply => format!("(reply to {})", self.name),
+        };
+        if self.required || self.kind == ArgKind::Reply {
+            usage
+        } else {
+            format!("[{}]", usage)
+        }
+    }
+}
+
+/// The arguments given to an action, parsed and validated against its argument schema.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// The values of given positional and rest arguments, by name.
+    values: HashMap<&'static str, String>,
+
+    /// The names of given flags.
+    flags: HashSet<&'static str>,
+
+    /// The message the command replies to, if the schema has a reply target.
+    reply: Option<MessageOrChannelPost>,
+}
+
+impl Args {
+    /// Parse the arguments of the given command message against the given schema.
+    ///
+    /// The command itself, such as `/cmd` or `/cmd@bot`, is skipped. An empty schema ignores any
+    /// input.
+    pub fn parse(schema: &[Arg], msg: &Message, text: &str) -> Result<Args, UsageError> {
+        let mut args = Args::default();
+        if schema.is_empty() {
+            return Ok(args);
+        }
+
+        let mut input = command_input(text);
+        let mut positionals = schema.iter().filter(|arg| arg.kind == ArgKind::Positional);
+        let rest = schema.iter().find(|arg| arg.kind == ArgKind::Rest);
+
+        loop {
+            input = input.trim_start();
+            if input.is_empty() {
+                break;
+            }
+
+            // Take declared flags
+            let word = input.split(char::is_whitespace).next().unwrap_or_default();
+            if let Some(name) = word.strip_prefix("--") {
+                if let Some(flag) = schema
+                    .iter()
+                    .find(|arg| arg.kind == ArgKind::Flag && arg.name.eq_ignore_ascii_case(name))
+                {
+                    args.flags.insert(flag.name);
+                    input = &input[word.len()..];
+                    continue;
+                }
+            }
+
+            // Take positional arguments, then take all remaining input as-is
+            if let Some(arg) = positionals.next() {
+                let (token, remaining) = next_token(input)?;
+                args.values.insert(arg.name, token);
+                input = remaining;
+            } else if let Some(arg) = rest {
+                args.values.insert(arg.name, input.trim_end().to_owned());
+                break;
+            } else if word.starts_with("--") {
+                return Err(UsageError::UnknownFlag(word.to_owned()));
+            } else {
+                return Err(UsageError::Unexpected(word.to_owned()));
+            }
+        }
+
+        // Take the reply target
+        if let Some(arg) = schema.iter().find(|arg| arg.kind == ArgKind::Reply) {
+            match &msg.reply_to_message {
+                Some(reply) => args.reply = Some((**reply).clone()),
+                None if arg.required => return Err(UsageError::MissingReply(arg.name)),
+                None => {}
+            }
+        }
+
+        // Ensure all required arguments are given
+        if let Some(arg) = schema.iter().find(|arg| {
+            arg.required
+                && (arg.kind == ArgKind::Positional || arg.kind == ArgKind::Rest)
+                && !args.values.contains_key(arg.name)
+        }) {
+            return Err(UsageError::Missing(arg.name));
+        }
+
+        Ok(args)
+    }
+
+    /// Get the value of the positional or rest argument with the given name, if given.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    /// Check whether the flag with the given name is given.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    /// Get the message the command replies to, if the schema has a reply target.
+    pub fn reply(&self) -> Option<&MessageOrChannelPost> {
+        self.reply.as_ref()
+    }
+}
+
+/// Build usage information for the given command and argument schema, such as
+/// `/duck <query...>`.
+pub fn usage(cmd: &str, schema: &[Arg]) -> String {
+    let mut usage = format!("/{}", cmd);
+    for arg in schema {
+        usage.push(' ');
+        usage += &arg.usage();
+    }
+    usage
+}
+
+/// Get the input of the given command message, being all text after the command itself.
+fn command_input(text: &str) -> &str {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => &text[end..],
+        None => "",
+    }
+}
+
+/// Take the next word or quoted string from the given input, which must not start with
+/// whitespace.
+///
+/// Returns the token along with the remaining input.
+fn next_token(input: &str) -> Result<(String, &str), UsageError> {
+    let quote = match input.chars().next() {
+        Some(quote @ '"') | Some(quote @ '\'') => quote,
+        _ => {
+            let end = input
+                .find(char::is_whitespace)
+                .unwrap_or_else(|| input.len());
+            return Ok((input[..end].to_owned(), &input[end..]));
+        }
+    };
+
+    // Take a quoted string, allowing escaped quotes
+    let mut token = String::new();
+    let mut escaped = false;
+    for (i, c) in input.char_indices().skip(1) {
+        match c {
+            _ if escaped => {
+                token.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            _ if c == quote => return Ok((token, &input[i + c.len_utf8()..])),
+            _ => token.push(c),
+        }
+    }
+    Err(UsageError::UnclosedQuote)
+}
+
+/// An argument usage error, reported to the user along with the command usage.
+#[derive(Debug, Fail)]
+pub enum UsageError {
+    /// A required argument is missing.
+    #[fail(display = "missing argument: {}", _0)]
+    Missing(&'static str),
+
+    /// The command must be sent as reply to a message.
+    #[fail(display = "reply to a message to use this command ({})", _0)]
+    MissingReply(&'static str),
+
+    /// An unknown flag was given.
+    #[fail(display = "unknown flag: {}", _0)]
+    UnknownFlag(String),
+
+    /// More arguments were given than accepted.
+    #[fail(display = "unexpected argument: {}", _0)]
+    Unexpected(String),
+
+    /// A quoted string is not closed.
+    #[fail(display = "missing closing quote")]
+    UnclosedQuote,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arg, Args, UsageError};
+    use crate::testing::{self, GROUP_ID};
+
+    /// Parse the given command against the given schema.
+    fn parse(schema: &[Arg], text: &str) -> Result<Args, UsageError> {
+        Args::parse(schema, &testing::message(GROUP_ID, text), text)
+    }
+
+    #[test]
+    fn empty_schema_ignores_input() {
+        assert!(parse(&[], "/ping").is_ok());
+        assert!(parse(&[], "/all meeting now").is_ok());
+        assert!(parse(&[], "/start 'payload").is_ok());
+    }
+
+    #[test]
+    fn takes_positional_and_quoted_arguments() {
+        let schema = &[
+            Arg::positional("alias", ""),
+            Arg::positional("command", "").optional(),
+        ];
+        let args = parse(schema, r#"/alias "a \"b\"" c"#).unwrap();
+        assert_eq!(args.get("alias"), Some(r#"a "b""#));
+        assert_eq!(args.get("command"), Some("c"));
+
+        let args = parse(schema, "/alias@riscbot a").unwrap();
+        assert_eq!(args.get("alias"), Some("a"));
+        assert_eq!(args.get("command"), None);
+    }
+
+    #[test]
+    fn takes_rest_as_is() {
+        let schema = &[Arg::flag("verbose", ""), Arg::rest("text", "")];
+        let args = parse(schema, "/exec --verbose echo 'hi  there\n--verbose ").unwrap();
+        assert!(args.flag("verbose"));
+        assert_eq!(args.get("text"), Some("echo 'hi  there\n--verbose"));
+
+        let args = parse(schema, r#"/echo "hello"#).unwrap();
+        assert!(!args.flag("verbose"));
+        assert_eq!(args.get("text"), Some(r#""hello"#));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let schema = &[Arg::flag("verbose", ""), Arg::positional("query", "")];
+        assert!(matches!(
+            parse(schema, "/cmd"),
+            Err(UsageError::Missing("query"))
+        ));
+        assert!(matches!(
+            parse(schema, "/cmd 'query"),
+            Err(UsageError::UnclosedQuote)
+        ));
+        assert!(matches!(
+            parse(schema, "/cmd query extra"),
+            Err(UsageError::Unexpected(ref token)) if token == "extra"
+        ));
+        assert!(matches!(
+            parse(schema, "/cmd query --quiet"),
+            Err(UsageError::UnknownFlag(ref flag)) if flag == "--quiet"
+        ));
+        assert!(matches!(
+            parse(&[Arg::reply("message", "")], "/rt"),
+            Err(UsageError::MissingReply("message"))
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The scope a cooldown policy limits invocations in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scope {
+    /// Each user is limited separately, across all chats.
+    User,
+
+    /// Each chat is limited separately, for all users together.
+    Chat,
+
+    /// All invocations are limited together.
+    Global,
+}
+
+/// A cooldown policy for an action.
+///
+/// Allows a burst of invocations within the period, further invocations in the same scope are
+/// refused until the oldest one is outside the period.
+#[derive(Copy, Clone, Debug)]
+pub struct Cooldown {
+    /// The scope invocations are limited in.
+    scope: Scope,
+
+    /// The number of invocations allowed within the period, always positive.
+    burst: usize,
+
+    /// The period in seconds.
+    period: u64,
+}
+
+impl Cooldown {
+    /// A cooldown allowing `burst` invocations per `period` seconds in the given scope.
+    ///
+    /// Panics if `burst` is zero, failing compilation for policies in constants.
+    const fn new(scope: Scope, burst: usize, period: u64) -> Self {
+        assert!(burst > 0, "cooldown burst must be positive");
+        Cooldown {
+            scope,
+            burst,
+            period,
+        }
+    }
+
+    /// A cooldown allowing each user `burst` invocations per `period` seconds.
+    pub const fn per_user(burst: usize, period: u64) -> Self {
+        Self::new(Scope::User, burst, period)
+    }
+
+    /// A cooldown allowing each chat `burst` invocations per `period` seconds.
+    pub const fn per_chat(burst: usize, period: u64) -> Self {
+        Self::new(Scope::Chat, burst, period)
+    }
+
+    /// A cooldown allowing `burst` invocations per `period` seconds in total.
+    pub const fn global(burst: usize, period: u64) -> Self {
+        Self::new(Scope::Global, burst, period)
+    }
+
+    /// Get the period as duration.
+    fn period(&self) -> Duration {
+        Duration::from_secs(self.period)
+    }
+
+    /// Get the key identifying the bucket of the given invocation in this scope.
+    fn key(&self, chat: i64, user: i64) -> i64 {
+        match self.scope {
+            Scope::User => user,
+            Scope::Chat => chat,
+            Scope::Global => 0,
+        }
+    }
+}
+
+/// The key of a cooldown bucket: the command, the index of its policy and the scope key.
+type BucketKey = (&'static str, usize, i64);
+
+/// Tracks recent command invocations, to enforce the cooldown policies of actions.
+pub struct Cooldowns {
+    /// The recent invocations of each bucket.
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+
+    /// The time until which no new cooldown notice is sent, by command, chat and user.
+    notices: Mutex<HashMap<(&'static str, i64, i64), Instant>>,
+}
+
+/// The recent invocations limited by a single cooldown policy.
+struct Bucket {
+    /// The period of the policy.
+    period: Duration,
+
+    /// The times of invocations within the period, oldest first.
+    times: VecDeque<Instant>,
+}
+
+impl Cooldowns {
+    /// Construct a new cooldown tracker, without any invocations yet.
+    pub fn new() -> Self {
+        Cooldowns {
+            buckets: Mutex::new(HashMap::new()),
+            notices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check whether the given command may be invoked by the given user in the given chat, and
+    /// record the invocation if so.
+    ///
+    /// If any of the policies refuses the invocation, nothing is recorded, and the time to wait
+    /// before it is allowed again is returned.
+    pub fn check(
+        &self,
+        cmd: &'static str,
+        policies: &[Cooldown],
+        chat: i64,
+        user: i64,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Forget invocations that are outside their period
+        buckets.retain(|_, bucket| {
+            let period = bucket.period;
+            bucket.times.retain(|at| now.duration_since(*at) < period);
+            !bucket.times.is_empty()
+        });
+
+        // Find the longest wait of all policies that are exhausted
+        let wait = policies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, policy)| {
+                let times = &buckets.get(&(cmd, i, policy.key(chat, user)))?.times;
+                if times.len() < policy.burst {
+                    return None;
+                }
+                let oldest = times[times.len() - policy.burst];
+                Some(policy.period() - now.duration_since(oldest))
+            })
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        for (i, policy) in policies.iter().enumerate() {
+            buckets
+                .entry((cmd, i, policy.key(chat, user)))
+                .or_insert_with(|| Bucket {
+                    period: policy.period(),
+                    times: VecDeque::new(),
+                })
+                .times
+                .push_back(now);
+        }
+        Ok(())
+    }
+
+    /// Check whether the given user should be notified about a refused invocation of the given
+    /// command, which must wait for the given time.
+    ///
+    /// A user is notified only once while the command is cooling down, to not spam the chat.
+    pub fn should_notify(&self, cmd: &'static str, chat: i64, user: i64, wait: Duration) -> bool {
+        let now = Instant::now();
+        let mut notices = self.notices.lock().unwrap();
+        notices.retain(|_, until| *until > now);
+
+        if notices.contains_key(&(cmd, chat, user)) {
+            return false;
+        }
+        notices.insert((cmd, chat, user), now + wait);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Cooldown, Cooldowns};
+
+    #[test]
+    fn refuses_after_burst() {
+        let cooldowns = Cooldowns::new();
+        let policies = &[Cooldown::per_user(2, 60)];
+        assert!(cooldowns.check("cmd", policies, 1, 1).is_ok());
+        assert!(cooldowns.check("cmd", policies, 1, 1).is_ok());
+
+        let wait = cooldowns.check("cmd", policies, 1, 1).unwrap_err();
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+
+        // Other users, and other commands, are limited separately
+        assert!(cooldowns.check("cmd", policies, 1, 2).is_ok());
+        assert!(cooldowns.check("other", policies, 1, 1).is_ok());
+    }
+
+    #[test]
+    fn refuses_if_any_policy_is_exhausted() {
+        let cooldowns = Cooldowns::new();
+        let policies = &[Cooldown::per_user(2, 60), Cooldown::per_chat(1, 60)];
+        assert!(cooldowns.check("cmd", policies, 1, 1).is_ok());
+        assert!(cooldowns.check("cmd", policies, 1, 2).is_err());
+        assert!(cooldowns.check("cmd", policies, 2, 1).is_ok());
+        assert!(cooldowns.check("cmd", policies, 3, 1).is_err());
+
+        // Refused invocations are not recorded
+        assert!(cooldowns.check("cmd", policies, 4, 2).is_ok());
+        assert!(cooldowns.check("cmd", policies, 5, 2).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "cooldown burst must be positive")]
+    fn rejects_zero_burst_policy() {
+        Cooldown::per_chat(0, 60);
+    }
+
+    #[test]
+    fn notifies_once_per_wait() {
+        let cooldowns = Cooldowns::new();
+        let wait = Duration::from_secs(60);
+        assert!(cooldowns.should_notify("cmd", 1, 1, wait));
+        assert!(!cooldowns.should_notify("cmd", 1, 1, wait));
+        assert!(cooldowns.should_notify("cmd", 1, 2, wait));
+    }
+}
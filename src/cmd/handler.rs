@@ -1,46 +1,255 @@
+use std::iter;
+use std::panic::AssertUnwindSafe;
+
+use futures::prelude::*;
+use htmlescape::encode_minimal;
 use regex::Regex;
-use telegram_bot::types::Message;
+use telegram_bot::{
+    prelude::*,
+    types::{Message, MessageChat, MessageKind, ParseMode},
+};
+use tracing::Span;
 
 use super::action::Error as ActionError;
-use super::action::ACTIONS;
+use super::action::{Action, ACTIONS};
+use super::alias;
+use super::args::{usage, Args};
+use super::permission::Error as PermissionError;
+use crate::alert::AlertKind;
+use crate::metrics;
+use crate::send::Error as SendError;
+use crate::settings::Feature;
 use crate::state::State;
+use crate::stats::TelegramToI64;
+use crate::util::{edit_distance, error_chain, panic_message};
 
 lazy_static! {
     /// A regex for matching messages that contain a command.
     static ref CMD_REGEX: Regex = Regex::new(
-        r"^/(?is)(?P<cmd>[A-Z0-9_]+)(@(?P<bot>[A-Z0-9_]+))?(\s.*$|$)",
+        r"^/(?is)(?P<cmd>\w+)(@(?P<bot>[A-Z0-9_]+))?(\s.*$|$)",
     ).expect("failed to compile CMD_REGEX");
 }
 
+/// The maximum edit distance of an unknown command to a known one, to suggest it.
+const MAX_SUGGEST_DISTANCE: usize = 2;
+
 /// The command handler.
 pub struct Handler;
 
 impl Handler {
     /// Handle the given command.
     pub async fn handle(state: State, cmd: &str, msg: Message) -> Result<(), Error> {
-        // Invoke the proper action
-        let action = ACTIONS.iter().find(|a| a.is_cmd(cmd));
+        // Invoke the proper action, by its name or a custom alias of the chat
+        let action = find_action(&state, cmd, &msg).await;
         if let Some(action) = action {
-            // Build the action invocation future
-            action.invoke(state, msg).await.map_err(|err| {
-                Error::Cmd(ActionError::Invoke {
-                    cause: err.compat(),
-                    name: action.cmd().to_owned(),
-                })
-            })
+            Span::current().record("action", &action.cmd());
+
+            // Ignore actions that are disabled in this chat
+            let feature = Feature::Action(action.cmd());
+            if !state
+                .settings()
+                .is_enabled(&state, msg.chat.id().to_i64(), feature)
+                .await
+            {
+                metrics::ACTION_INVOCATIONS
+                    .with_label_values(&[action.cmd(), "disabled"])
+                    .inc();
+                debug!("ignoring action that is disabled in this chat");
+                return Ok(());
+            }
+
+            // Ensure the user has the role required for the action
+            let role = state.config().bot.role(action.cmd(), action.role());
+            if !state
+                .permissions()
+                .allows(&state, role, &msg.chat, msg.from.id.to_i64())
+                .await
+                .map_err(Error::Permission)?
+            {
+                metrics::ACTION_INVOCATIONS
+                    .with_label_values(&[action.cmd(), "denied"])
+                    .inc();
+                return state
+                    .send(msg.text_reply(format!(
+                        "Sorry, /{} is only available to {}.",
+                        action.cmd(),
+                        role,
+                    )))
+                    .map_ok(|_| ())
+                    .map_err(Error::Denied)
+                    .await;
+            }
+
+            // Parse the arguments, reply with usage information if invalid
+            let text = match &msg.kind {
+                MessageKind::Text { data, .. } => data.as_str(),
+                _ => "",
+            };
+            let args = match Args::parse(action.args(), &msg, text) {
+                Ok(args) => args,
+                Err(err) => {
+                    metrics::ACTION_INVOCATIONS
+                        .with_label_values(&[action.cmd(), "usage"])
+                        .inc();
+                    let mut response = format!(
+                        "{}\n\nUsage: <code>{}</code>",
+                        encode_minimal(&capitalize(&err.to_string())),
+                        encode_minimal(&usage(action.cmd(), action.args())),
+                    );
+                    if !action.hidden() {
+                        response += &format!("\nSend /help {} for details.", action.cmd());
+                    }
+                    return state
+                        .send(msg.text_reply(response).parse_mode(ParseMode::Html))
+                        .map_ok(|_| ())
+                        .map_err(Error::Usage)
+                        .await;
+                }
+            };
+
+            // Enforce the action cooldowns, tell the user when to try again once
+            let (chat, user) = (msg.chat.id().to_i64(), msg.from.id.to_i64());
+            if !state.config().bot.is_owner(user) {
+                if let Err(wait) =
+                    state
+                        .cooldowns()
+                        .check(action.cmd(), action.cooldowns(), chat, user)
+                {
+                    metrics::ACTION_INVOCATIONS
+                        .with_label_values(&[action.cmd(), "cooldown"])
+                        .inc();
+                    if !state
+                        .cooldowns()
+                        .should_notify(action.cmd(), chat, user, wait)
+                    {
+                        return Ok(());
+                    }
+                    return state
+                        .send(msg.text_reply(format!(
+                            "Please slow down, try /{} again in {}s.",
+                            action.cmd(),
+                            wait.as_secs() + 1,
+                        )))
+                        .map_ok(|_| ())
+                        .map_err(Error::Cooldown)
+                        .await;
+                }
+            }
+
+            // Invoke the action, measure its latency and result
+            let timer = metrics::ACTION_DURATION
+                .with_label_values(&[action.cmd()])
+                .start_timer();
+            let result = AssertUnwindSafe(action.invoke(state.clone(), msg, args))
+                .catch_unwind()
+                .await;
+            timer.observe_duration();
+            let outcome = match &result {
+                Ok(Ok(())) => "ok",
+                Ok(Err(_)) => "error",
+                Err(_) => "panic",
+            };
+            metrics::ACTION_INVOCATIONS
+                .with_label_values(&[action.cmd(), outcome])
+                .inc();
+
+            match result {
+                Ok(result) => result.map_err(|err| {
+                    // Alert operators about the failure, grouped by command and error
+                    state
+                        .alerts()
+                        .record(AlertKind::Action, format!("/{}: {}", action.cmd(), err));
+                    Error::Cmd(ActionError::Invoke {
+                        cause: err.compat(),
+                        name: action.cmd().to_owned(),
+                    })
+                }),
+                Err(payload) => {
+                    // Report panics like any other error, the panic hook alerts operators
+                    let message = panic_message(&*payload);
+                    metrics::PANICS.inc();
+                    error!(panic = %message, "action panicked");
+                    Err(Error::Cmd(ActionError::Panic {
+                        message,
+                        name: action.cmd().to_owned(),
+                    }))
+                }
+            }
         } else {
-            Ok(())
+            suggest_cmd(&state, cmd, &msg).await
         }
     }
 }
 
+/// Find the action for the given command name or alias, or a custom alias of the message chat.
+async fn find_action(
+    state: &State,
+    cmd: &str,
+    msg: &Message,
+) -> Option<&'static (dyn Action + Sync)> {
+    if let Some(action) = ACTIONS.iter().find(|a| a.is_cmd(cmd)) {
+        return Some(action.as_ref());
+    }
+
+    match alias::resolve(state, msg.chat.id().to_i64(), cmd).await {
+        Ok(Some(target)) => ACTIONS
+            .iter()
+            .find(|a| a.is_cmd(&target))
+            .map(|action| action.as_ref()),
+        Ok(None) => None,
+        Err(err) => {
+            warn!(error = %error_chain(&err), "failed to resolve chat alias");
+            None
+        }
+    }
+}
+
+/// Suggest a known command if the given unknown command closely resembles it.
+///
+/// Only suggests in private chats, or if the command is addressed to this bot explicitly, as
+/// other bots in a group may know the command.
+async fn suggest_cmd(state: &State, cmd: &str, msg: &Message) -> Result<(), Error> {
+    let addressed = match &msg.kind {
+        MessageKind::Text { data, .. } => data
+            .split_whitespace()
+            .next()
+            .map_or(false, |token| token.contains('@')),
+        _ => false,
+    };
+    if !addressed && !matches!(msg.chat, MessageChat::Private(_)) {
+        return Ok(());
+    }
+
+    // Find the closest visible command name or alias
+    let cmd = alias::normalize(cmd);
+    let closest = ACTIONS
+        .iter()
+        .filter(|action| !action.hidden())
+        .flat_map(|action| iter::once(action.cmd()).chain(action.aliases().iter().copied()))
+        .map(|name| (edit_distance(&cmd, name), name))
+        .min();
+    let name = match closest {
+        Some((distance, name))
+            if distance <= MAX_SUGGEST_DISTANCE && distance < cmd.chars().count() =>
+        {
+            name
+        }
+        _ => return Ok(()),
+    };
+
+    state
+        .send(msg.text_reply(format!("Unknown command /{}, did you mean /{}?", cmd, name)))
+        .map_ok(|_| ())
+        .map_err(Error::Suggest)
+        .await
+}
+
 /// Test wether the given message is recognized as a command.
 /// If a specific bot is given with a `@bot` suffix, commands only match if the given bot name\
-/// equals the name of this bot.
+/// equals the name of this bot, given as `bot`.
 ///
 /// The actual command name is returned if it is, `None` otherwise.
-// TODO: dynamically load the bot name from the API
-pub fn matches_cmd(msg: &str) -> Option<&str> {
+pub fn matches_cmd<'a>(msg: &'a str, bot: &str) -> Option<&'a str> {
     match CMD_REGEX.captures(msg.trim()) {
         Some(groups) => {
             // Get the command
@@ -51,7 +260,7 @@ pub fn matches_cmd(msg: &str) -> Option<&str> {
 
             // Ensure the bot name matches if one is given
             match groups.name("bot") {
-                Some(bot) if bot.as_str() == "riscbot" => Some(cmd),
+                Some(name) if name.as_str().eq_ignore_ascii_case(bot) => Some(cmd),
                 None => Some(cmd),
                 _ => None,
             }
@@ -60,12 +269,41 @@ pub fn matches_cmd(msg: &str) -> Option<&str> {
     }
 }
 
+/// Capitalize the first letter of the given text.
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 /// A command handler error.
 #[derive(Debug, Fail)]
 pub enum Error {
     /// An error occurred while handling a command.
     #[fail(display = "failed to invoke command")]
     Cmd(#[cause] ActionError),
+
+    /// An error occurred while suggesting a command for an unknown command.
+    #[fail(display = "failed to send command suggestion")]
+    Suggest(#[cause] SendError),
+
+    /// Failed to check whether the user may invoke a command.
+    #[fail(display = "failed to check command permissions")]
+    Permission(#[cause] PermissionError),
+
+    /// An error occurred while telling the user a command is not available to them.
+    #[fail(display = "failed to send command denial")]
+    Denied(#[cause] SendError),
+
+    /// An error occurred while telling the user a command is cooling down.
+    #[fail(display = "failed to send command cooldown notice")]
+    Cooldown(#[cause] SendError),
+
+    /// An error occurred while replying with command usage information.
+    #[fail(display = "failed to send command usage")]
+    Usage(#[cause] SendError),
 }
 
 /// Convert command action errors to a command handler error.
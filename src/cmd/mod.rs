@@ -1,2 +1,6 @@
 pub mod action;
+pub mod alias;
+pub mod args;
+pub mod cooldown;
 pub mod handler;
+pub mod permission;
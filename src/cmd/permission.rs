@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use failure::SyncFailure;
+use serde::Deserialize;
+use telegram_bot::{
+    types::{GetChatAdministrators, MessageChat},
+    Error as TelegramError,
+};
+
+use crate::state::State;
+use crate::stats::TelegramToI64;
+
+/// The role a user must have to invoke an action.
+///
+/// Bot owners have every role.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Anyone may invoke the action.
+    Anyone,
+
+    /// Only administrators of the chat may invoke the action. In a private chat, the user counts
+    /// as administrator.
+    ChatAdmin,
+
+    /// Only users on the configured allowlist may invoke the action.
+    Allowlisted,
+
+    /// Only bot owners may invoke the action.
+    Owner,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Role::Anyone => "everyone",
+            Role::ChatAdmin => "chat admins",
+            Role::Allowlisted => "allowlisted users",
+            Role::Owner => "bot owners",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Checks whether users have the role required to invoke an action.
+///
+/// The administrators of each chat are fetched from Telegram, and cached for the configured time.
+pub struct Permissions {
+    /// The user IDs of the administrators of each chat along with the time they were fetched, by
+    /// chat ID.
+    admins: Mutex<HashMap<i64, (Instant, Vec<i64>)>>,
+}
+
+impl Permissions {
+    /// Construct a new permission checker, with nothing cached yet.
+    pub fn new() -> Self {
+        Permissions {
+            admins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check whether the given user has the given role in the given chat.
+    pub async fn allows(
+        &self,
+        state: &State,
+        role: Role,
+        chat: &MessageChat,
+        user: i64,
+    ) -> Result<bool, Error> {
+        let config = &state.config().bot;
+        if config.is_owner(user) {
+            return Ok(true);
+        }
+
+        Ok(match role {
+            Role::Anyone => true,
+            Role::ChatAdmin => match chat {
+                MessageChat::Private(_) => true,
+                chat => self.chat_admins(state, chat).await?.contains(&user),
+            },
+            Role::Allowlisted => config.is_allowlisted(user),
+            Role::Owner => false,
+        })
+    }
+
+    /// Get the user IDs of the administrators of the given chat, fetch them if not cached.
+    async fn chat_admins(&self, state: &State, chat: &MessageChat) -> Result<Vec<i64>, Error> {
+        let id = chat.id().to_i64();
+        let ttl = state.config().bot.admin_cache();
+
+        // Use the cached administrators if still fresh
+        let cached = {
+            let admins = self.admins.lock().unwrap();
+            admins
+                .get(&id)
+                .filter(|(fetched_at, _)| fetched_at.elapsed() < ttl)
+                .map(|(_, admins)| admins.clone())
+        };
+        if let Some(admins) = cached {
+            return Ok(admins);
+        }
+
+        // Fetch the administrators, cache them and forget stale chats
+        let admins: Vec<i64> = state
+            .telegram_client()
+            .send(GetChatAdministrators::new(chat))
+            .await
+            .map_err(|err| Error::Admins(SyncFailure::new(err)))?
+            .iter()
+            .map(|member| member.user.id.to_i64())
+            .collect();
+        let mut cache = self.admins.lock().unwrap();
+        cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < ttl);
+        cache.insert(id, (Instant::now(), admins.clone()));
+        Ok(admins)
+    }
+}
+
+/// A permission error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to fetch the administrators of a chat.
+    #[fail(display = "failed to fetch chat administrators")]
+    Admins(#[cause] SyncFailure<TelegramError>),
+}
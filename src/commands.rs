@@ -0,0 +1,83 @@
+use crate::cmd::action::{Action, ACTIONS};
+use crate::cmd::permission::Role;
+use crate::requests::{BotCommand, BotCommandScope, SetMyCommands};
+use crate::state::State;
+
+/// The scopes a command list is published for.
+const SCOPES: [BotCommandScope; 4] = [
+    BotCommandScope::Default,
+    BotCommandScope::AllPrivateChats,
+    BotCommandScope::AllGroupChats,
+    BotCommandScope::AllChatAdministrators,
+];
+
+/// Publish the list of visible commands to Telegram, for command autocomplete.
+///
+/// A list is published for each scope, and for each language with translated descriptions.
+/// Failures are logged, and don't stop the bot from running.
+pub async fn publish(state: &State) {
+    let config = &state.config().commands;
+    if !config.publish {
+        return;
+    }
+
+    let languages = std::iter::once(None).chain(config.descriptions.keys().map(Some));
+    let (mut lists, mut failed) = (0, 0);
+    for language in languages {
+        for &scope in SCOPES.iter() {
+            let commands = build_list(state, scope, language.map(|l| l.as_str()));
+            let request = SetMyCommands::new(commands, scope, language.cloned());
+            lists += 1;
+            if let Err(err) = state.telegram_client().send(request).await {
+                warn!(%err, ?scope, ?language, "failed to publish command list");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed == 0 {
+        info!(lists, "published command list");
+    } else {
+        warn!(lists, failed, "failed to publish some command lists");
+    }
+}
+
+/// Build the command list for the given scope, with descriptions in the given language.
+///
+/// Descriptions that are not translated fall back to the action help.
+fn build_list(state: &State, scope: BotCommandScope, language: Option<&str>) -> Vec<BotCommand> {
+    let config = &state.config().commands;
+    let descriptions = language.and_then(|language| config.descriptions.get(language));
+    ACTIONS
+        .iter()
+        .filter(|action| {
+            let role = state.config().bot.role(action.cmd(), action.role());
+            is_listed(action.as_ref(), role, scope, config.scopes)
+        })
+        .map(|action| {
+            let description = descriptions
+                .and_then(|descriptions| descriptions.get(action.cmd()))
+                .map_or(action.help(), |description| description.as_str());
+            BotCommand::new(action.cmd().into(), description.into())
+        })
+        .collect()
+}
+
+/// Check whether the given action, requiring the given role, is listed in the given scope.
+///
+/// Commands only chat administrators may use are listed for them and in private chats, if
+/// scoped lists are enabled. Hidden and owner only commands are never listed.
+fn is_listed(
+    action: &(dyn Action + Sync),
+    role: Role,
+    scope: BotCommandScope,
+    scoped: bool,
+) -> bool {
+    if action.hidden() || role == Role::Owner {
+        return false;
+    }
+    match scope {
+        BotCommandScope::AllPrivateChats | BotCommandScope::AllChatAdministrators if scoped => true,
+        _ => role != Role::ChatAdmin,
+    }
+}
@@ -0,0 +1,886 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::Error as IoError;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::de::Error as TomlError;
+use tracing_subscriber::EnvFilter;
+
+use crate::cmd::action::ACTIONS;
+use crate::cmd::permission::Role;
+
+/// The environment variable to load a custom configuration file path from.
+const CONFIG_PATH_ENV: &str = "RISC_CONFIG";
+
+/// The default configuration file path, used if it exists.
+const CONFIG_PATH_DEFAULT: &str = "config.toml";
+
+/// The prefix for environment variables overriding configuration values.
+const ENV_PREFIX: &str = "RISC_";
+
+/// The application configuration.
+///
+/// Loaded from a TOML file, and overridden by environment variables afterwards.
+/// All values fall back to sane defaults if not set.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Logging configuration.
+    pub log: LogConfig,
+
+    /// Telegram API configuration.
+    pub telegram: TelegramConfig,
+
+    /// Bot configuration.
+    pub bot: BotConfig,
+
+    /// Outbound Telegram rate limit configuration.
+    pub rate_limit: RateLimitConfig,
+
+    /// Telegram update handling configuration.
+    pub updates: UpdatesConfig,
+
+    /// Webhook configuration, used when updates are received in webhook mode.
+    pub webhook: WebhookConfig,
+
+    /// Database configuration.
+    pub database: DatabaseConfig,
+
+    /// Operations HTTP server configuration.
+    pub server: ServerConfig,
+
+    /// Health check configuration.
+    pub health: HealthConfig,
+
+    /// Message stats configuration.
+    pub stats: StatsConfig,
+
+    /// Configuration for the `/exec` action.
+    pub exec: ExecConfig,
+
+    /// Configuration for the isolated Docker environment commands are executed in.
+    pub docker: DockerConfig,
+
+    /// Graceful shutdown configuration.
+    pub shutdown: ShutdownConfig,
+
+    /// Configuration for replying to users with errors.
+    pub errors: ErrorsConfig,
+
+    /// Operator alert configuration.
+    pub alerts: AlertsConfig,
+
+    /// Configuration for publishing the command list to Telegram.
+    pub commands: CommandsConfig,
+}
+
+impl Config {
+    /// Load the configuration.
+    ///
+    /// The file at the path in `RISC_CONFIG` is loaded, or `config.toml` if it exists.
+    /// Defaults are used if no file is found. Environment variables are applied on top.
+    pub fn load() -> Result<Config, Error> {
+        let mut config = match Self::path() {
+            Some(path) => Self::from_file(&path)?,
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Find the configuration file path to load, if any.
+    pub fn path() -> Option<PathBuf> {
+        match env::var_os(CONFIG_PATH_ENV) {
+            Some(path) => Some(path.into()),
+            None => Some(PathBuf::from(CONFIG_PATH_DEFAULT)).filter(|path| path.is_file()),
+        }
+    }
+
+    /// Load the configuration from the given TOML file, without applying environment variables.
+    pub fn from_file(path: &Path) -> Result<Config, Error> {
+        let data = fs::read_to_string(path).map_err(|cause| Error::Read {
+            path: path.display().to_string(),
+            cause,
+        })?;
+        toml::from_str(&data).map_err(|cause| Error::Parse {
+            path: path.display().to_string(),
+            cause,
+        })
+    }
+
+    /// Override configuration values with environment variables that are set.
+    ///
+    /// A variable is named after the section and key, such as `RISC_EXEC_TIMEOUT`.
+    fn apply_env(&mut self) -> Result<(), Error> {
+        env_override(&mut self.log.level, "LOG_LEVEL")?;
+        env_override(&mut self.log.format, "LOG_FORMAT")?;
+        env_override(&mut self.telegram.transport, "TELEGRAM_TRANSPORT")?;
+        env_override_list(&mut self.bot.owners, "BOT_OWNERS")?;
+        env_override_list(&mut self.bot.allowlist, "BOT_ALLOWLIST")?;
+        env_override(&mut self.bot.admin_cache, "BOT_ADMIN_CACHE")?;
+        env_override(&mut self.rate_limit.enabled, "RATE_LIMIT_ENABLED")?;
+        env_override(
+            &mut self.rate_limit.global_per_second,
+            "RATE_LIMIT_GLOBAL_PER_SECOND",
+        )?;
+        env_override(
+            &mut self.rate_limit.private_per_second,
+            "RATE_LIMIT_PRIVATE_PER_SECOND",
+        )?;
+        env_override(
+            &mut self.rate_limit.group_per_minute,
+            "RATE_LIMIT_GROUP_PER_MINUTE",
+        )?;
+        env_override(&mut self.rate_limit.max_retries, "RATE_LIMIT_MAX_RETRIES")?;
+        env_override(&mut self.updates.mode, "UPDATES_MODE")?;
+        env_override(&mut self.updates.max_concurrent, "UPDATES_MAX_CONCURRENT")?;
+        env_override(&mut self.updates.max_queued, "UPDATES_MAX_QUEUED")?;
+        env_override(&mut self.webhook.listen, "WEBHOOK_LISTEN")?;
+        env_override(&mut self.webhook.path, "WEBHOOK_PATH")?;
+        env_override_opt(&mut self.webhook.secret_token, "WEBHOOK_SECRET_TOKEN")?;
+        env_override_opt(&mut self.webhook.url, "WEBHOOK_URL")?;
+        env_override(&mut self.database.migrate, "DATABASE_MIGRATE")?;
+        env_override(&mut self.server.enabled, "SERVER_ENABLED")?;
+        env_override(&mut self.server.listen, "SERVER_LISTEN")?;
+        env_override(&mut self.health.max_poll_age, "HEALTH_MAX_POLL_AGE")?;
+        env_override(&mut self.stats.flush_interval, "STATS_FLUSH_INTERVAL")?;
+        env_override(&mut self.exec.timeout, "EXEC_TIMEOUT")?;
+        env_override(&mut self.exec.output_truncate, "EXEC_OUTPUT_TRUNCATE")?;
+        env_override_list(&mut self.exec.throttle, "EXEC_THROTTLE")?;
+        env_override(&mut self.docker.image, "DOCKER_IMAGE")?;
+        env_override(&mut self.docker.cpus, "DOCKER_CPUS")?;
+        env_override(&mut self.docker.pids_limit, "DOCKER_PIDS_LIMIT")?;
+        env_override(&mut self.docker.stop_timeout, "DOCKER_STOP_TIMEOUT")?;
+        env_override(&mut self.docker.kill_after, "DOCKER_KILL_AFTER")?;
+        env_override(&mut self.shutdown.timeout, "SHUTDOWN_TIMEOUT")?;
+        env_override(&mut self.errors.cooldown, "ERRORS_COOLDOWN")?;
+        env_override(&mut self.errors.dedup_window, "ERRORS_DEDUP_WINDOW")?;
+        env_override_opt(&mut self.alerts.chat, "ALERTS_CHAT")?;
+        env_override(&mut self.alerts.interval, "ALERTS_INTERVAL")?;
+        env_override(&mut self.commands.publish, "COMMANDS_PUBLISH")?;
+        env_override(&mut self.commands.scopes, "COMMANDS_SCOPES")?;
+        Ok(())
+    }
+
+    /// Validate the configuration, to catch invalid combinations of values early.
+    fn validate(&self) -> Result<(), Error> {
+        if EnvFilter::try_new(&self.log.level).is_err() {
+            return Err(Error::Invalid(format!(
+                "log.level '{}' is not a valid level or filter",
+                self.log.level
+            )));
+        }
+        if self.updates.mode == UpdateMode::Webhook && self.webhook.secret_token.is_none() {
+            return Err(Error::Invalid(
+                "webhook.secret_token must be set when using webhook update mode".into(),
+            ));
+        }
+        if self.rate_limit.global_per_second <= 0.0
+            || self.rate_limit.private_per_second <= 0.0
+            || self.rate_limit.group_per_minute <= 0.0
+        {
+            return Err(Error::Invalid("rate_limit rates must be positive".into()));
+        }
+        if self.updates.max_concurrent == 0 || self.updates.max_queued == 0 {
+            return Err(Error::Invalid(
+                "updates.max_concurrent and updates.max_queued must be positive".into(),
+            ));
+        }
+        if self.stats.flush_interval == 0 {
+            return Err(Error::Invalid(
+                "stats.flush_interval must be positive".into(),
+            ));
+        }
+        if self.alerts.interval == 0 {
+            return Err(Error::Invalid("alerts.interval must be positive".into()));
+        }
+        if let Some(language) = self.commands.descriptions.keys().find(|language| {
+            language.len() != 2 || !language.chars().all(|c| c.is_ascii_lowercase())
+        }) {
+            return Err(Error::Invalid(format!(
+                "commands.descriptions language '{}' must be a two-letter ISO 639-1 code",
+                language
+            )));
+        }
+        if !self.webhook.path.starts_with('/') {
+            return Err(Error::Invalid("webhook.path must start with a /".into()));
+        }
+        if let Some(cmd) = self
+            .bot
+            .roles
+            .keys()
+            .find(|cmd| !ACTIONS.iter().any(|action| action.cmd() == cmd.as_str()))
+        {
+            return Err(Error::Invalid(format!(
+                "bot.roles command '{}' is not a known command, use its name without slash",
+                cmd
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Logging configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// The level to log at, such as `info` or `debug`, or a filter such as `risc_bot=debug`.
+    pub level: String,
+
+    /// The format to log in.
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".into(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+/// The format to log in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable text lines.
+    Text,
+
+    /// A JSON object per line, including fields of the surrounding spans.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}'", format)),
+        }
+    }
+}
+
+/// Telegram API configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// The transport to send Telegram API requests through.
+    pub transport: TransportKind,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        TelegramConfig {
+            transport: TransportKind::Api,
+        }
+    }
+}
+
+/// Bot configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    /// Telegram user IDs of the bot owners.
+    pub owners: Vec<i64>,
+
+    /// Telegram user IDs of users allowed to use commands with the allowlisted role.
+    pub allowlist: Vec<i64>,
+
+    /// The number of seconds to cache the administrators of a chat.
+    pub admin_cache: u64,
+
+    /// The role required to invoke an action, by command, overriding the role of the action.
+    pub roles: HashMap<String, Role>,
+}
+
+impl BotConfig {
+    /// Check whether the user with the given Telegram ID is a bot owner.
+    pub fn is_owner(&self, user: i64) -> bool {
+        self.owners.contains(&user)
+    }
+
+    /// Check whether the user with the given Telegram ID is allowlisted.
+    ///
+    /// Bot owners are always allowlisted.
+    pub fn is_allowlisted(&self, user: i64) -> bool {
+        self.is_owner(user) || self.allowlist.contains(&user)
+    }
+
+    /// Get the role required to invoke the action with the given command.
+    ///
+    /// Returns the configured role, or the given default role of the action if none.
+    pub fn role(&self, cmd: &str, default: Role) -> Role {
+        self.roles.get(cmd).copied().unwrap_or(default)
+    }
+
+    /// The time to cache the administrators of a chat.
+    pub fn admin_cache(&self) -> Duration {
+        Duration::from_secs(self.admin_cache)
+    }
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            owners: Vec::new(),
+            allowlist: Vec::new(),
+            admin_cache: 300,
+            roles: HashMap::new(),
+        }
+    }
+}
+
+/// The transport to send Telegram API requests through.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// Send requests to the real Telegram Bot API.
+    Api,
+
+    /// Record requests in memory without sending anything, for offline use.
+    Fake,
+}
+
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind.to_lowercase().as_str() {
+            "api" => Ok(TransportKind::Api),
+            "fake" => Ok(TransportKind::Fake),
+            _ => Err(format!("unknown transport '{}'", kind)),
+        }
+    }
+}
+
+/// Outbound Telegram rate limit configuration.
+///
+/// See: <https://core.telegram.org/bots/faq#my-bot-is-hitting-limits-how-do-i-avoid-this>
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Whether to rate limit sending messages.
+    pub enabled: bool,
+
+    /// The maximum number of messages to send per second, over all chats.
+    pub global_per_second: f64,
+
+    /// The maximum number of messages to send per second to a single private chat.
+    pub private_per_second: f64,
+
+    /// The maximum number of messages to send per minute to a single group or channel.
+    pub group_per_minute: f64,
+
+    /// The maximum number of times to retry a request after a flood error.
+    pub max_retries: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            global_per_second: 30.0,
+            private_per_second: 1.0,
+            group_per_minute: 20.0,
+            max_retries: 3,
+        }
+    }
+}
+
+/// Telegram update handling configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdatesConfig {
+    /// How updates are received from Telegram.
+    pub mode: UpdateMode,
+
+    /// Maximum number of updates handled concurrently.
+    pub max_concurrent: usize,
+
+    /// Maximum number of updates queued or being handled, before receiving updates is paused.
+    pub max_queued: usize,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        UpdatesConfig {
+            mode: UpdateMode::Polling,
+            max_concurrent: 4,
+            max_queued: 100,
+        }
+    }
+}
+
+/// How updates are received from Telegram.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// Long poll the Telegram API for new updates.
+    Polling,
+
+    /// Receive updates pushed by Telegram on an embedded HTTP server.
+    Webhook,
+}
+
+impl FromStr for UpdateMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "polling" => Ok(UpdateMode::Polling),
+            "webhook" => Ok(UpdateMode::Webhook),
+            _ => Err(format!("unknown update mode '{}'", mode)),
+        }
+    }
+}
+
+/// Webhook configuration, used when updates are received in webhook mode.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The address to listen on for webhook requests.
+    pub listen: SocketAddr,
+
+    /// The HTTP path webhook requests are accepted on.
+    pub path: String,
+
+    /// The secret token Telegram sends along with each webhook request, required.
+    pub secret_token: Option<String>,
+
+    /// The public URL Telegram should send updates to.
+    ///
+    /// If set, the webhook is registered with Telegram at startup. Otherwise it must be
+    /// registered manually.
+    pub url: Option<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            listen: ([127, 0, 0, 1], 8443).into(),
+            path: "/".into(),
+            secret_token: None,
+            url: None,
+        }
+    }
+}
+
+/// Database configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Whether pending database migrations are applied at startup.
+    pub migrate: MigrationMode,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            migrate: MigrationMode::Auto,
+        }
+    }
+}
+
+/// Whether pending database migrations are applied at startup.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    /// Apply pending migrations automatically.
+    Auto,
+
+    /// Only check the schema version, and refuse to start if migrations are pending.
+    Check,
+}
+
+impl FromStr for MigrationMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "auto" => Ok(MigrationMode::Auto),
+            "check" => Ok(MigrationMode::Check),
+            _ => Err(format!("unknown migration mode '{}'", mode)),
+        }
+    }
+}
+
+/// Operations HTTP server configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Whether to run the operations server, serving metrics and health checks.
+    pub enabled: bool,
+
+    /// The address to listen on for operations requests, such as metrics scrapes.
+    pub listen: SocketAddr,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            enabled: true,
+            listen: ([127, 0, 0, 1], 9184).into(),
+        }
+    }
+}
+
+/// Health check configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// The maximum number of seconds since the last successful update poll to be healthy.
+    ///
+    /// Polling pauses while all update handlers are busy, so a bot that stays saturated this long
+    /// is reported unhealthy as well.
+    pub max_poll_age: u64,
+}
+
+impl HealthConfig {
+    /// The maximum time since the last successful update poll to be healthy.
+    pub fn max_poll_age(&self) -> Duration {
+        Duration::from_secs(self.max_poll_age)
+    }
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig { max_poll_age: 120 }
+    }
+}
+
+/// Message stats configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// Interval in seconds to flush queued stats to the database at.
+    pub flush_interval: u64,
+}
+
+impl StatsConfig {
+    /// The interval to flush queued stats to the database at.
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.flush_interval)
+    }
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        StatsConfig { flush_interval: 60 }
+    }
+}
+
+/// Configuration for the `/exec` action.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecConfig {
+    /// The timeout in seconds for commands being executed.
+    pub timeout: u64,
+
+    /// The number of characters to truncate the output log at.
+    pub output_truncate: usize,
+
+    /// The time in seconds to wait before each next status message update.
+    ///
+    /// The n-th update waits for the n-th entry, the last entry is used for all updates after it.
+    /// This gradually increases to prevent hitting the Telegram rate limit for message edits.
+    pub throttle: Vec<u64>,
+}
+
+impl ExecConfig {
+    /// The timeout duration for commands being executed.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// The time in seconds to wait before sending the status update with the given index.
+    pub fn throttle_secs(&self, update: usize) -> u64 {
+        self.throttle
+            .get(update)
+            .or_else(|| self.throttle.last())
+            .cloned()
+            .unwrap_or(1)
+    }
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        ExecConfig {
+            timeout: 300,
+            output_truncate: 4096 - 150,
+            throttle: vec![1, 1, 3, 3, 3, 5, 5, 5, 10],
+        }
+    }
+}
+
+/// Configuration for the isolated Docker environment commands are executed in.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DockerConfig {
+    /// The Docker image to run commands in.
+    pub image: String,
+
+    /// The number of CPUs a container may use.
+    pub cpus: f64,
+
+    /// The maximum number of processes in a container.
+    pub pids_limit: u32,
+
+    /// The number of seconds Docker waits for a container to stop before killing it.
+    pub stop_timeout: u64,
+
+    /// The number of seconds added to the timeout, after which a signalled command is killed.
+    pub kill_after: u64,
+}
+
+impl Default for DockerConfig {
+    fn default() -> Self {
+        DockerConfig {
+            image: "risc-exec".into(),
+            cpus: 0.2,
+            pids_limit: 64,
+            stop_timeout: 1,
+            kill_after: 5,
+        }
+    }
+}
+
+/// Graceful shutdown configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// The maximum number of seconds to wait for running handlers to complete when quitting.
+    pub timeout: u64,
+}
+
+impl ShutdownConfig {
+    /// The maximum time to wait for running handlers to complete when quitting.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { timeout: 30 }
+    }
+}
+
+/// Configuration for replying to users with errors.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorsConfig {
+    /// The minimum number of seconds between error replies in a single chat.
+    pub cooldown: u64,
+
+    /// The number of seconds in which the same error is replied only once in a single chat.
+    pub dedup_window: u64,
+}
+
+impl ErrorsConfig {
+    /// The minimum time between error replies in a single chat.
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown)
+    }
+
+    /// The time in which the same error is replied only once in a single chat.
+    pub fn dedup_window(&self) -> Duration {
+        Duration::from_secs(self.dedup_window)
+    }
+}
+
+impl Default for ErrorsConfig {
+    fn default() -> Self {
+        ErrorsConfig {
+            cooldown: 30,
+            dedup_window: 600,
+        }
+    }
+}
+
+/// Operator alert configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// The Telegram chat ID to send operator alerts to, alerts are disabled if not set.
+    pub chat: Option<i64>,
+
+    /// The number of seconds to group alerts in, before sending them.
+    pub interval: u64,
+}
+
+impl AlertsConfig {
+    /// The time to group alerts in, before sending them.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig {
+            chat: None,
+            interval: 60,
+        }
+    }
+}
+
+/// Configuration for publishing the command list to Telegram.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    /// Whether to publish the command list at startup, for command autocomplete in Telegram.
+    pub publish: bool,
+
+    /// Whether to publish separate lists for private chats and chat administrators, including
+    /// the commands only they may use.
+    pub scopes: bool,
+
+    /// Translated command descriptions by command name, by language code.
+    ///
+    /// Each language is published as separate list for users with that language.
+    pub descriptions: HashMap<String, HashMap<String, String>>,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        CommandsConfig {
+            publish: true,
+            scopes: true,
+            descriptions: HashMap::new(),
+        }
+    }
+}
+
+/// Override the given configuration value with the prefixed environment variable if set.
+fn env_override<T>(target: &mut T, key: &str) -> Result<(), Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let key = format!("{}{}", ENV_PREFIX, key);
+    if let Ok(value) = env::var(&key) {
+        *target = value.trim().parse().map_err(|err: T::Err| Error::Env {
+            key,
+            reason: err.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Override the given optional configuration value with the prefixed environment variable if set.
+///
+/// An empty variable unsets the value.
+fn env_override_opt<T>(target: &mut Option<T>, key: &str) -> Result<(), Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let key = format!("{}{}", ENV_PREFIX, key);
+    if let Ok(value) = env::var(&key) {
+        let value = value.trim();
+        *target = if value.is_empty() {
+            None
+        } else {
+            Some(value.parse().map_err(|err: T::Err| Error::Env {
+                key,
+                reason: err.to_string(),
+            })?)
+        };
+    }
+    Ok(())
+}
+
+/// Override the given configuration list with the prefixed environment variable if set.
+///
+/// List items are separated by a comma.
+fn env_override_list<T>(target: &mut Vec<T>, key: &str) -> Result<(), Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let key = format!("{}{}", ENV_PREFIX, key);
+    if let Ok(value) = env::var(&key) {
+        *target = value
+            .split(',')
+            .map(|item| item.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|err: T::Err| Error::Env {
+                key,
+                reason: err.to_string(),
+            })?;
+    }
+    Ok(())
+}
+
+/// A configuration error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to read the configuration file.
+    #[fail(display = "failed to read configuration file: {}", path)]
+    Read {
+        /// The path of the configuration file.
+        path: String,
+
+        /// The internal cause.
+        #[cause]
+        cause: IoError,
+    },
+
+    /// Failed to parse the configuration file.
+    #[fail(display = "failed to parse configuration file: {}", path)]
+    Parse {
+        /// The path of the configuration file.
+        path: String,
+
+        /// The internal cause.
+        #[cause]
+        cause: TomlError,
+    },
+
+    /// A configuration environment variable has an invalid value.
+    #[fail(display = "invalid value for environment variable {}: {}", key, reason)]
+    Env {
+        /// The environment variable.
+        key: String,
+
+        /// The reason the value is invalid.
+        reason: String,
+    },
+
+    /// The configuration is invalid.
+    #[fail(display = "invalid configuration: {}", _0)]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Error};
+    use crate::cmd::permission::Role;
+
+    #[test]
+    fn accepts_known_role_overrides() {
+        let mut config = Config::default();
+        config.bot.roles.insert("all".into(), Role::Anyone);
+        config.bot.roles.insert("exec".into(), Role::Owner);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_role_overrides() {
+        for cmd in &["exce", "/exec"] {
+            let mut config = Config::default();
+            config.bot.roles.insert((*cmd).into(), Role::Allowlisted);
+            assert!(matches!(config.validate(), Err(Error::Invalid(_))));
+        }
+    }
+}
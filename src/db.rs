@@ -0,0 +1,208 @@
+use std::env;
+use std::fmt;
+use std::time::Duration;
+
+use diesel::{
+    connection::SimpleConnection,
+    mysql::MysqlConnection,
+    pg::PgConnection,
+    r2d2::{
+        ConnectionManager, CustomizeConnection, Error as R2d2Error, Pool, PoolError,
+        PooledConnection,
+    },
+    result::Error as DieselError,
+    sqlite::SqliteConnection,
+};
+use tokio::task::JoinError;
+
+/// Pragmas applied to each new SQLite connection.
+///
+/// Foreign keys are disabled by default in SQLite, and a busy timeout is required to share a
+/// database file between pooled connections.
+const SQLITE_PRAGMAS: &str = "PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;";
+
+/// Run the given expression with the backend specific connection of a pooled connection.
+///
+/// The expression is expanded for each backend, so Diesel queries can be used as-is:
+///
+/// ```ignore
+/// with_connection!(connection, |conn| chat::table.load::<Chat>(conn))
+/// ```
+macro_rules! with_connection {
+    ($pooled:expr, |$conn:ident| $body:expr) => {
+        match &$pooled {
+            $crate::db::DbPooled::Mysql(pooled) => {
+                let $conn: &::diesel::mysql::MysqlConnection = &**pooled;
+                $body
+            }
+            $crate::db::DbPooled::Postgres(pooled) => {
+                let $conn: &::diesel::pg::PgConnection = &**pooled;
+                $body
+            }
+            $crate::db::DbPooled::Sqlite(pooled) => {
+                let $conn: &::diesel::sqlite::SqliteConnection = &**pooled;
+                $body
+            }
+        }
+    };
+}
+
+/// A supported database backend.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Mysql,
+    Postgres,
+    Sqlite,
+}
+
+impl Backend {
+    /// Select the backend for the given database URL, based on its scheme.
+    ///
+    /// Returns the backend along with the URL to connect with, `None` if the scheme is unknown.
+    pub fn from_url(url: &str) -> Option<(Backend, &str)> {
+        if url.starts_with("mysql://") {
+            Some((Backend::Mysql, url))
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some((Backend::Postgres, url))
+        } else if url.starts_with("sqlite://") {
+            Some((Backend::Sqlite, &url["sqlite://".len()..]))
+        } else {
+            None
+        }
+    }
+
+    /// The name of the backend.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Mysql => "mysql",
+            Backend::Postgres => "postgres",
+            Backend::Sqlite => "sqlite",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A database connection pool, for any of the supported backends.
+#[derive(Clone)]
+pub enum DbPool {
+    Mysql(Pool<ConnectionManager<MysqlConnection>>),
+    Postgres(Pool<ConnectionManager<PgConnection>>),
+    Sqlite(Pool<ConnectionManager<SqliteConnection>>),
+}
+
+impl DbPool {
+    /// Create a connection pool for the given database URL.
+    ///
+    /// The backend is selected by the scheme of the URL, such as `mysql://`, `postgres://` or
+    /// `sqlite://`. Connections are established to test the database is reachable.
+    pub fn connect(url: &str) -> Result<DbPool, Error> {
+        let (backend, url) = Backend::from_url(url).ok_or(Error::UnknownBackend)?;
+        let pool = match backend {
+            Backend::Mysql => Pool::builder()
+                .build(ConnectionManager::new(url))
+                .map(DbPool::Mysql),
+            Backend::Postgres => Pool::builder()
+                .build(ConnectionManager::new(url))
+                .map(DbPool::Postgres),
+            Backend::Sqlite => Pool::builder()
+                .connection_customizer(Box::new(SqliteCustomizer))
+                .build(ConnectionManager::new(url))
+                .map(DbPool::Sqlite),
+        };
+        pool.map_err(|err| Error::Connect(backend, err))
+    }
+
+    /// Create a connection pool for the database URL in the `DATABASE_URL` environment variable.
+    pub fn from_env() -> Result<DbPool, Error> {
+        let url = env::var("DATABASE_URL").map_err(|_| Error::NoUrl)?;
+        Self::connect(&url)
+    }
+
+    /// Get a connection from the pool.
+    ///
+    /// This blocks until a connection is available, or the pool timeout is reached.
+    pub fn get(&self) -> Result<DbPooled, PoolError> {
+        match self {
+            DbPool::Mysql(pool) => pool.get().map(DbPooled::Mysql),
+            DbPool::Postgres(pool) => pool.get().map(DbPooled::Postgres),
+            DbPool::Sqlite(pool) => pool.get().map(DbPooled::Sqlite),
+        }
+    }
+
+    /// Get a connection from the pool, waiting at most for the given timeout.
+    pub fn get_timeout(&self, timeout: Duration) -> Result<DbPooled, PoolError> {
+        match self {
+            DbPool::Mysql(pool) => pool.get_timeout(timeout).map(DbPooled::Mysql),
+            DbPool::Postgres(pool) => pool.get_timeout(timeout).map(DbPooled::Postgres),
+            DbPool::Sqlite(pool) => pool.get_timeout(timeout).map(DbPooled::Sqlite),
+        }
+    }
+
+    /// The backend of this pool.
+    pub fn backend(&self) -> Backend {
+        match self {
+            DbPool::Mysql(_) => Backend::Mysql,
+            DbPool::Postgres(_) => Backend::Postgres,
+            DbPool::Sqlite(_) => Backend::Sqlite,
+        }
+    }
+}
+
+/// A pooled database connection, for any of the supported backends.
+///
+/// Use `with_connection!` to run queries on it.
+pub enum DbPooled {
+    Mysql(PooledConnection<ConnectionManager<MysqlConnection>>),
+    Postgres(PooledConnection<ConnectionManager<PgConnection>>),
+    Sqlite(PooledConnection<ConnectionManager<SqliteConnection>>),
+}
+
+/// Customizer to configure each new SQLite connection in the pool.
+#[derive(Debug)]
+struct SqliteCustomizer;
+
+impl CustomizeConnection<SqliteConnection, R2d2Error> for SqliteCustomizer {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), R2d2Error> {
+        conn.batch_execute(SQLITE_PRAGMAS)
+            .map_err(R2d2Error::QueryError)
+    }
+}
+
+/// A database error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// The database URL environment variable is not set.
+    #[fail(display = "env var DATABASE_URL not set")]
+    NoUrl,
+
+    /// The database URL has an unknown scheme.
+    #[fail(
+        display = "unknown database backend, URL must start with mysql://, postgres:// or sqlite://"
+    )]
+    UnknownBackend,
+
+    /// Failed to connect to the database.
+    #[fail(display = "failed to connect to {} database", _0)]
+    Connect(Backend, #[cause] PoolError),
+}
+
+/// A database query error.
+#[derive(Debug, Fail)]
+pub enum QueryError {
+    /// Failed to get a database connection from the pool.
+    #[fail(display = "failed to get database connection from pool")]
+    Connect(#[cause] PoolError),
+
+    /// The query failed.
+    #[fail(display = "failed to query database")]
+    Query(#[cause] DieselError),
+
+    /// The blocking query task failed.
+    #[fail(display = "failed to run database query")]
+    Blocking(#[cause] JoinError),
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use futures::prelude::*;
+use telegram_bot::types::{Integer, MessageOrChannelPost, UpdateKind};
+use tokio::runtime::Handle;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Semaphore,
+};
+
+use crate::metrics;
+use crate::state::State;
+use crate::stats::TelegramToI64;
+use crate::util::panic_message;
+
+/// A job handling a single update.
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Dispatches update handling jobs on a bounded pool of workers.
+///
+/// Jobs for the same chat are handled in the order they were dispatched, one at a time. Jobs for
+/// different chats are handled concurrently, up to the configured maximum. Dispatching waits while
+/// the maximum number of queued jobs is reached, to apply backpressure to the update stream.
+///
+/// Clones share the same pool.
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+/// The shared dispatcher state.
+struct Inner {
+    /// The global state.
+    state: State,
+
+    /// A handle to the runtime to spawn workers on.
+    handle: Handle,
+
+    /// Permits for jobs that are queued or running, bounding the queue.
+    queue: Arc<Semaphore>,
+
+    /// Permits for jobs that are running, bounding concurrency.
+    workers: Arc<Semaphore>,
+
+    /// The job queue of each chat that has a worker, by chat ID.
+    chats: Mutex<HashMap<Integer, UnboundedSender<Job>>>,
+}
+
+impl Dispatcher {
+    /// Construct a new dispatcher, using the limits from the update configuration.
+    pub fn new(state: State, handle: Handle) -> Self {
+        let config = &state.config().updates;
+        let queue = Arc::new(Semaphore::new(config.max_queued));
+        let workers = Arc::new(Semaphore::new(config.max_concurrent));
+        Dispatcher {
+            inner: Arc::new(Inner {
+                state,
+                handle,
+                queue,
+                workers,
+                chats: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Dispatch the given job, to be handled in order with other jobs for the same chat.
+    ///
+    /// Jobs without a chat are not ordered. This waits while the queue is full. The job is tracked
+    /// for shutdown as soon as it is queued.
+    pub async fn dispatch<F>(&self, chat: Option<Integer>, job: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // Wait for room in the queue
+        let permit = self
+            .inner
+            .queue
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("dispatcher queue closed");
+        metrics::UPDATES_QUEUED.inc();
+
+        // Release the queue permit once the job completes, keep the worker alive on panic
+        let state = self.inner.state.clone();
+        let job: Job = Box::pin(self.inner.state.shutdown().track(async move {
+            if let Err(payload) = AssertUnwindSafe(job).catch_unwind().await {
+                metrics::PANICS.inc();
+                error!(panic = %panic_message(&*payload), "update handler panicked");
+            }
+            metrics::UPDATES_QUEUED.dec();
+            state.health().record_progress();
+            drop(permit);
+        }));
+
+        match chat {
+            Some(chat) => self.enqueue(chat, job),
+            None => {
+                let workers = self.inner.workers.clone();
+                self.inner.handle.spawn(async move {
+                    let _worker = workers.acquire_owned().await;
+                    job.await;
+                });
+            }
+        }
+    }
+
+    /// Add the given job to the queue of the given chat, start a worker for it if there is none.
+    fn enqueue(&self, chat: Integer, job: Job) {
+        let mut chats = self.inner.chats.lock().unwrap();
+
+        // Queue on the running worker for this chat
+        let job = match chats.get(&chat) {
+            Some(sender) => match sender.send(job) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(job)) => job,
+            },
+            None => job,
+        };
+
+        // Start a new worker
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let _ = sender.send(job);
+        chats.insert(chat, sender);
+        self.inner.handle.spawn(self.clone().work(chat, receiver));
+    }
+
+    /// Handle the queued jobs of a chat one by one, until its queue is empty.
+    async fn work(self, chat: Integer, mut receiver: UnboundedReceiver<Job>) {
+        loop {
+            // Take the next job, remove the queue while locked if empty so no job is lost
+            let job = {
+                let mut chats = self.inner.chats.lock().unwrap();
+                match receiver.try_recv() {
+                    Ok(job) => job,
+                    Err(_) => {
+                        chats.remove(&chat);
+                        return;
+                    }
+                }
+            };
+
+            let _worker = self.inner.workers.acquire().await;
+            job.await;
+        }
+    }
+}
+
+/// Get the chat the given update belongs to, to order its handling by.
+pub fn update_chat(kind: &UpdateKind) -> Option<Integer> {
+    match kind {
+        UpdateKind::Message(message) | UpdateKind::EditedMessage(message) => {
+            Some(message.chat.id().to_i64())
+        }
+        UpdateKind::CallbackQuery(query) => match &query.message {
+            Some(MessageOrChannelPost::Message(message)) => Some(message.chat.id().to_i64()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -1,14 +1,33 @@
-use std::process::ExitStatus;
+use std::process::{self, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::{normal, Error};
+use crate::config::Config;
 use tokio::process::Command;
 
+/// Counter to generate unique container names with.
+static CONTAINER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Generate a unique name for a container to execute a command in.
+pub fn container_name() -> String {
+    format!(
+        "risc-exec-{}-{}",
+        process::id(),
+        CONTAINER_COUNTER.fetch_add(1, Ordering::SeqCst),
+    )
+}
+
 /// Execute the given command in a secure isolated environment.
 ///
 /// `stdout` and `stderr` is streamed line by line to the `output` closure,
 /// which is called for each line that received.
+///
+/// The isolated environment is configured through the given `config`, and the container is
+/// given the `name` to kill it with.
 pub async fn execute<O>(
+    config: &Config,
+    name: &str,
     cmd: String,
     reply_text: Option<String>,
     cmd_text: Option<String>,
@@ -24,23 +43,24 @@ where
     let isolated_cmd = isolated_cmd
         .arg("run")
         .arg("--rm")
+        .args(&["--name", name])
         .args(&["--workdir", "/root"])
         .args(&["--restart", "no"]);
 
     // Configure limits
-    // TODO: configurable timeout
     // TODO: also handle a timeout fallback outside the actual container
     // TODO: map container UIDs to something above 10k
+    let docker = &config.docker;
     isolated_cmd
-        .args(&["--stop-timeout", "1"])
-        .args(&["--cpus", "0.2"])
+        .args(&["--stop-timeout", &docker.stop_timeout.to_string()])
+        .args(&["--cpus", &docker.cpus.to_string()])
         // TODO: enable these memory limits once the warning is fixed
         // .args(&["--memory", "100m"])
         // .args(&["--kernel-memory", "25m"])
         // .args(&["--memory-swappiness", "0"])
         // .args(&["--device-read-bps", "/:50mb"])
         // .args(&["--device-write-bps", "/:50mb"])
-        .args(&["--pids-limit", "64"]);
+        .args(&["--pids-limit", &docker.pids_limit.to_string()]);
 
     // Add reply and cmd text variables
     if let Some(text) = reply_text {
@@ -51,9 +71,15 @@ where
     }
 
     // Select image and binary to run
+    let timeout = config.exec.timeout;
     isolated_cmd
-        .arg("risc-exec")
-        .args(&["timeout", "--signal=SIGTERM", "--kill-after=305", "300"])
+        .arg(&docker.image)
+        .args(&[
+            "timeout",
+            "--signal=SIGTERM",
+            &format!("--kill-after={}", timeout + docker.kill_after),
+            &timeout.to_string(),
+        ])
         .args(&["bash", "-c", &cmd]);
 
     // Execute the isolated command in the normal environment
@@ -64,6 +90,7 @@ where
 ///
 /// The `stdout` and `stderr` is collected and returned with the future.
 pub async fn execute_sync(
+    config: &Config,
     cmd: String,
     reply_text: Option<String>,
     cmd_text: Option<String>,
@@ -73,12 +100,31 @@ pub async fn execute_sync(
     let buf_exec = buf.clone();
 
     // Execute the sed command, fill the buffer, stringify the buffer and return
-    let status = execute(cmd, reply_text, cmd_text, move |out| {
-        buf_exec.lock().unwrap().push_str(&out);
-        Ok(())
-    })
+    let status = execute(
+        config,
+        &container_name(),
+        cmd,
+        reply_text,
+        cmd_text,
+        move |out| {
+            buf_exec.lock().unwrap().push_str(&out);
+            Ok(())
+        },
+    )
     .await?;
 
     let buf = buf.lock().unwrap().to_owned();
     Ok((buf, status))
 }
+
+/// Kill the running container with the given name, to interrupt the command executed in it.
+pub async fn kill(name: &str) -> Result<(), Error> {
+    Command::new("docker")
+        .args(&["kill", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map(|_| ())
+        .map_err(Error::Kill)
+}
@@ -20,4 +20,8 @@ pub enum Error {
     /// An error occurred while waiting for the spawned user command complete.
     #[fail(display = "failed to wait for user command to complete")]
     Complete(#[cause] IoError),
+
+    /// An error occurred while killing the container of an interrupted user command.
+    #[fail(display = "failed to kill user command container")]
+    Kill(#[cause] IoError),
 }
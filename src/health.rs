@@ -0,0 +1,181 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::task;
+
+use crate::config::UpdateMode;
+use crate::state::State;
+
+/// The maximum time to wait for a database connection while checking health.
+const DB_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tracks the health of the bot, as reported by its components.
+pub struct Health {
+    inner: Mutex<Inner>,
+}
+
+/// The recorded health state.
+#[derive(Default)]
+struct Inner {
+    /// The time Telegram was last polled for updates successfully.
+    last_poll: Option<Instant>,
+
+    /// The time an update was last handled, reported to tell a busy bot from a stalled one.
+    last_progress: Option<Instant>,
+
+    /// Whether the webhook server is running, to receive updates on in webhook mode.
+    webhook_running: bool,
+
+    /// Whether the last stats flush succeeded, `None` if stats were not flushed yet.
+    last_flush: Option<bool>,
+}
+
+impl Health {
+    /// Construct a new health tracker, nothing is recorded yet.
+    pub fn new() -> Self {
+        Health {
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Record that Telegram was polled for updates successfully.
+    pub fn record_poll(&self) {
+        self.inner.lock().unwrap().last_poll = Some(Instant::now());
+    }
+
+    /// Record that an update was handled.
+    ///
+    /// This is reported separately, and doesn't affect health.
+    pub fn record_progress(&self) {
+        self.inner.lock().unwrap().last_progress = Some(Instant::now());
+    }
+
+    /// Record whether the webhook server is running.
+    pub fn record_webhook(&self, running: bool) {
+        self.inner.lock().unwrap().webhook_running = running;
+    }
+
+    /// Record whether the last stats flush succeeded.
+    pub fn record_flush(&self, success: bool) {
+        self.inner.lock().unwrap().last_flush = Some(success);
+    }
+
+    /// Check the health of all components.
+    pub async fn check(&self, state: &State) -> Report {
+        let updates = self.check_updates(state);
+        let database = Self::check_database(state).await;
+        let stats_flush = self.check_flush();
+        let last_handled = self
+            .inner
+            .lock()
+            .unwrap()
+            .last_progress
+            .map(|at| at.elapsed().as_secs());
+        Report {
+            healthy: updates.ok && database.ok && stats_flush.ok,
+            checks: Checks {
+                updates,
+                database,
+                stats_flush,
+            },
+            last_handled,
+        }
+    }
+
+    /// Check whether updates are being received from Telegram.
+    ///
+    /// In polling mode, this only depends on the last successful poll.
+    fn check_updates(&self, state: &State) -> Check {
+        let inner = self.inner.lock().unwrap();
+        match state.config().updates.mode {
+            UpdateMode::Polling => {
+                let max_age = state.config().health.max_poll_age();
+                match inner.last_poll.map(|at| at.elapsed()) {
+                    Some(age) if age <= max_age => {
+                        Check::ok(format!("last polled {}s ago", age.as_secs()))
+                    }
+                    Some(age) => Check::fail(format!("last polled {}s ago", age.as_secs())),
+                    None => Check::fail("not polled yet"),
+                }
+            }
+            UpdateMode::Webhook if inner.webhook_running => Check::ok("webhook server running"),
+            UpdateMode::Webhook => Check::fail("webhook server not running"),
+        }
+    }
+
+    /// Check whether the database pool can hand out a connection.
+    async fn check_database(state: &State) -> Check {
+        let db = state.db().clone();
+        match task::spawn_blocking(move || db.get_timeout(DB_TIMEOUT).map(|_| ())).await {
+            Ok(Ok(())) => Check::ok("connection available"),
+            Ok(Err(err)) => Check::fail(err.to_string()),
+            Err(err) => Check::fail(err.to_string()),
+        }
+    }
+
+    /// Check whether the last stats flush succeeded.
+    fn check_flush(&self) -> Check {
+        match self.inner.lock().unwrap().last_flush {
+            Some(true) => Check::ok("last flush succeeded"),
+            Some(false) => Check::fail("last flush failed"),
+            None => Check::ok("not flushed yet"),
+        }
+    }
+}
+
+/// A health report.
+#[derive(Serialize)]
+pub struct Report {
+    /// Whether all checks passed.
+    pub healthy: bool,
+
+    /// The result of each check.
+    pub checks: Checks,
+
+    /// The number of seconds since an update was last handled, `None` if none were handled yet.
+    ///
+    /// This shows whether the bot is making progress, but doesn't affect health.
+    pub last_handled: Option<u64>,
+}
+
+/// The result of each health check.
+#[derive(Serialize)]
+pub struct Checks {
+    /// Whether updates are being received from Telegram.
+    pub updates: Check,
+
+    /// Whether the database is reachable.
+    pub database: Check,
+
+    /// Whether stats are flushed to the database.
+    pub stats_flush: Check,
+}
+
+/// The result of a single health check.
+#[derive(Serialize)]
+pub struct Check {
+    /// Whether the check passed.
+    pub ok: bool,
+
+    /// A human readable detail of the check result.
+    pub detail: String,
+}
+
+impl Check {
+    /// A passed check with the given detail.
+    fn ok(detail: impl Into<String>) -> Self {
+        Check {
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    /// A failed check with the given detail.
+    fn fail(detail: impl Into<String>) -> Self {
+        Check {
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
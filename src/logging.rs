@@ -0,0 +1,18 @@
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::config::{LogConfig, LogFormat};
+
+/// Initialize the global logger with the given configuration.
+///
+/// The `RUST_LOG` environment variable takes precedence over the configured level if set, to
+/// allow fine-grained filters such as `risc_bot=debug,hyper=warn`.
+pub fn init(config: &LogConfig) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+
+    let builder = fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    match config.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
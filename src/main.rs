@@ -1,38 +1,75 @@
 #[macro_use]
 extern crate diesel;
 #[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
 extern crate failure;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate tracing;
+
+#[macro_use]
+mod db;
 
+mod alert;
 mod app;
+mod cli;
 mod cmd;
+mod commands;
+mod config;
+mod dispatch;
 mod executor;
+mod health;
+mod logging;
+mod metrics;
+mod migrations;
 mod models;
 mod msg;
+mod report;
+mod requests;
 mod schema;
+mod send;
+mod server;
+mod settings;
+mod shutdown;
 mod state;
 mod stats;
+#[cfg(test)]
+mod testing;
 pub mod traits;
+mod transport;
+mod updates;
 mod util;
+mod webhook;
 
-use std::time::Duration;
+use std::panic::AssertUnwindSafe;
+use std::process;
 
 use dotenv::dotenv;
 use futures::future;
 use futures::prelude::*;
-use telegram_bot::types::UpdateKind;
+use futures::stream::LocalBoxStream;
+use telegram_bot::{
+    types::{Update, UpdateKind},
+    Error as TelegramError,
+};
 use tokio::pin;
 use tokio::runtime::Handle;
-use tokio::signal::ctrl_c;
 use tokio_stream::wrappers::IntervalStream;
+use tracing::{field, Instrument, Span};
 
-use msg::handler::Handler;
+use alert::AlertKind;
+use cli::{Command, Error as CliError};
+use cmd::action::settings as settings_action;
+use config::{Config, Error as ConfigError, UpdateMode};
+use dispatch::Dispatcher;
+use msg::handler::{Error as HandlerError, Handler};
+use server::Error as ServerError;
 use state::State;
-use util::handle_msg_error;
-
-/// Maximum number of updates handled concurrently.
-const MAX_CONCURRENT_UPDATES: usize = 4;
+use stats::TelegramToI64;
+use util::{error_chain, handle_msg_error, panic_message, print_error};
+use webhook::Error as WebhookError;
 
 /// The application entrypoint.
 #[tokio::main]
@@ -40,89 +77,238 @@ async fn main() {
     // Load the environment variables file
     dotenv().ok();
 
+    // Parse the command line arguments
+    let command = Command::from_args();
+
+    // Load the configuration
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            print_error::<ConfigError>(err);
+            process::exit(1);
+        }
+    };
+    logging::init(&config.log);
+
+    // Invoke the selected command
+    let result = match command {
+        Command::Run => {
+            run(config).await;
+            Ok(())
+        }
+        Command::Migrate { check } => cli::migrate::invoke(check),
+        Command::CheckConfig => cli::check_config::invoke(&config),
+        Command::StatsDump { chat, user } => cli::stats::dump(chat, user),
+        Command::ChatsList => cli::chats::list(),
+        Command::Send { chat, text } => cli::send::invoke(config, chat, text).await,
+    };
+    if let Err(err) = result {
+        print_error::<CliError>(err);
+        process::exit(1);
+    }
+}
+
+/// Run the bot, until a signal to quit is received.
+async fn run(config: Config) {
     // Initialize the global state
-    let state = State::init(Handle::current());
+    let state = State::init(config, Handle::current()).await;
+    alert::install_panic_hook(state.clone());
+
+    // Start the operations server
+    if let Err(err) = server::spawn(&state, &Handle::current()) {
+        print_error::<ServerError>(err);
+        process::exit(1);
+    }
+
+    // Publish the list of commands, for autocomplete in Telegram clients
+    commands::publish(&state).await;
+
+    // Start receiving Telegram updates
+    let updates = match build_updates(&state, &Handle::current()).await {
+        Ok(updates) => updates,
+        Err(err) => {
+            print_error::<WebhookError>(err);
+            process::exit(1);
+        }
+    };
 
-    // Build a signal handling future to quit nicely
-    let signal = ctrl_c().inspect(|_| eprintln!("Received CTRL+C signal, preparing to quit..."));
+    // Run the application until a signal to quit is received
+    let app = build_application(state.clone(), updates, Handle::current());
+    let signal = shutdown::signal_quit();
     pin!(signal);
+    future::select(app, signal).await;
 
-    // Build the application, attach signal handling
-    let app = build_application(state.clone(), Handle::current());
-    let app = future::select(app, signal).then(|_| {
-        state.stats().flush(state.db());
-        eprintln!("Flushed stats to database");
-        eprintln!("Quitting...");
-        future::ready(())
-    });
+    // Stop accepting updates, wait for running handlers to complete
+    state.shutdown().trigger();
+    let running = state.shutdown().running();
+    if running > 0 {
+        info!(running, "waiting for running handlers to complete");
+    }
+    let timeout = state.config().shutdown.timeout();
+    if !state.shutdown().drain(timeout).await {
+        warn!(
+            running = state.shutdown().running(),
+            "timed out waiting for running handlers, quitting anyway",
+        );
+    }
 
-    // Run the application future in the reactor
-    app.await
+    // Flush the stats one final time
+    state.stats().flush(state.db());
+    info!("flushed stats to database, quitting");
 }
 
 /// Build the future for running the main application, which is the bot.
-fn build_application(state: State, handle: Handle) -> impl Future<Output = ()> + Unpin {
+fn build_application(
+    state: State,
+    updates: UpdateStream,
+    handle: Handle,
+) -> impl Future<Output = ()> + Unpin {
     let stats_flusher = build_stats_flusher(state.clone());
-    let telegram = build_telegram_handler(state, handle);
-    future::select(telegram, stats_flusher).map(|_| ())
+    let alert_sender = alert::build_sender(state.clone()).boxed_local();
+    let telegram = build_telegram_handler(state, updates, handle).boxed_local();
+    future::select(telegram, future::select(stats_flusher, alert_sender)).map(|_| ())
+}
+
+/// A stream of Telegram updates.
+type UpdateStream = LocalBoxStream<'static, Result<Update, TelegramError>>;
+
+/// Build the stream of Telegram updates, received through the configured update mode.
+///
+/// In polling mode, a webhook registered before is removed first.
+async fn build_updates(state: &State, handle: &Handle) -> Result<UpdateStream, WebhookError> {
+    match state.config().updates.mode {
+        UpdateMode::Polling => {
+            webhook::delete(state).await?;
+            Ok(updates::poll(state.clone()).boxed_local())
+        }
+        UpdateMode::Webhook => Ok(webhook::updates(state, handle).await?.map(Ok).boxed_local()),
+    }
 }
 
 /// Build a future for handling Telegram API updates.
-fn build_telegram_handler(state: State, handle: Handle) -> impl Future<Output = ()> {
-    state.telegram_client().stream().for_each_concurrent(
-        self::MAX_CONCURRENT_UPDATES,
-        move |update| {
-            // Clone the state to get ownership
-            let state = state.clone();
+///
+/// Updates are dispatched to a bounded pool of workers, handling the updates of each chat in order.
+/// Receiving updates is paused while the pool is saturated.
+fn build_telegram_handler(
+    state: State,
+    updates: UpdateStream,
+    handle: Handle,
+) -> impl Future<Output = ()> {
+    let dispatcher = Dispatcher::new(state.clone(), handle);
+    updates.for_each(move |update| {
+        // Clone the state and dispatcher to get ownership
+        let state = state.clone();
+        let dispatcher = dispatcher.clone();
 
+        async move {
             // Unpack update
             // TODO: return errors?
             let update = match update {
                 Ok(update) => update,
                 Err(err) => {
-                    eprintln!("ERR: Telegram API updates loop error, ignoring: {}", err);
-                    return future::ready(());
+                    error!(%err, "Telegram API updates loop error, ignoring");
+                    return;
                 }
             };
 
-            // Process messages
-            match update.kind {
-                UpdateKind::Message(message) => {
-                    // Update the message stats
-                    state.stats().increase_message_stats(&message, 1, 0);
-
-                    // Build the message handling future, handle any errors
-                    let msg_handler =
-                        Handler::handle(state.clone(), message.clone()).or_else(|err| {
-                            handle_msg_error(state, message, err).map_err(|err| {
-                                eprintln!(
-                                    "ERR: failed to handle error while handling message: {:?}",
-                                    err,
-                                );
-                            })
-                        });
-
-                    // Spawn the message handler future on the runtime
-                    handle.spawn(msg_handler);
+            metrics::UPDATES
+                .with_label_values(&[metrics::update_kind_label(&update.kind)])
+                .inc();
+
+            // Dispatch the update, in order with other updates of the same chat
+            let chat = dispatch::update_chat(&update.kind);
+            dispatcher
+                .dispatch(chat, handle_update(state, update))
+                .await;
+        }
+    })
+}
+
+/// Handle a single Telegram API update.
+async fn handle_update(state: State, update: Update) {
+    // Create a span to log everything related to this update in
+    let span = info_span!(
+        "update",
+        update_id = update.id,
+        chat_id = field::Empty,
+        user_id = field::Empty,
+        message_id = field::Empty,
+        action = field::Empty,
+    );
+
+    async move {
+        // Keep polling from acknowledging the update until it is handled
+        let update_id = update.id;
+        let _in_flight = state.updates().guard(update_id);
+
+        // Skip updates that were already handled, such as before a restart
+        if !updates::pending(&state, update_id).await {
+            info!("skipping update that was already handled");
+            return;
+        }
+
+        // Process messages
+        match update.kind {
+            UpdateKind::Message(message) => {
+                let span = Span::current();
+                span.record("chat_id", &message.chat.id().to_i64());
+                span.record("user_id", &message.from.id.to_i64());
+                span.record("message_id", &message.id.to_i64());
+
+                // Update the message stats
+                state.stats().increase_message_stats(&message, 1, 0);
+
+                // Handle the message, catch panics to handle them like any other error
+                let result = AssertUnwindSafe(Handler::handle(state.clone(), message.clone()))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|payload| {
+                        let panic = panic_message(&*payload);
+                        metrics::PANICS.inc();
+                        error!(%panic, "message handler panicked");
+                        Err(HandlerError::Panic(panic))
+                    });
+                if let Err(err) = result {
+                    if let Err(err) = handle_msg_error(state.clone(), message, err).await {
+                        error!(?err, "failed to handle error while handling message");
+                    }
                 }
-                UpdateKind::EditedMessage(message) => {
-                    state.stats().increase_message_stats(&message, 0, 1);
+            }
+            UpdateKind::EditedMessage(message) => {
+                state.stats().increase_message_stats(&message, 0, 1);
+            }
+            UpdateKind::CallbackQuery(query) => {
+                Span::current().record("user_id", &query.from.id.to_i64());
+
+                // Handle the callback query, only the settings keyboard uses them
+                if let Err(err) = settings_action::handle_callback(state.clone(), query).await {
+                    error!(error = %error_chain(&err), "failed to handle callback query");
                 }
-                _ => {}
             }
+            _ => {}
+        }
 
-            future::ready(())
-        },
-    )
+        // Mark the update handled, so it isn't fetched again after a restart
+        updates::finish(&state, update_id).await;
+    }
+    .instrument(span)
+    .await
 }
 
 /// Build a future for handling Telegram API updates.
 ///
 /// Returned future never completes.
 fn build_stats_flusher(state: State) -> impl Future<Output = ()> {
-    let interval = tokio::time::interval(Duration::from_secs(60));
+    let interval = tokio::time::interval(state.config().stats.flush_interval());
     IntervalStream::new(interval).for_each(move |_| {
-        state.stats().flush(state.db());
+        let flushed = state.stats().flush(state.db());
+        state.health().record_flush(flushed);
+        if !flushed {
+            state.alerts().record(
+                AlertKind::StatsFlush,
+                "failed to flush queued stats to database",
+            );
+        }
         future::ready(())
     })
 }
@@ -0,0 +1,99 @@
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    TextEncoder,
+};
+use telegram_bot::types::UpdateKind;
+
+lazy_static! {
+    /// The number of updates received from Telegram, by update kind.
+    pub static ref UPDATES: IntCounterVec = register_int_counter_vec!(
+        "risc_updates_total",
+        "Number of updates received from Telegram, by update kind",
+        &["kind"]
+    ).expect("failed to register UPDATES metric");
+
+    /// The number of updates queued or being handled.
+    pub static ref UPDATES_QUEUED: IntGauge = register_int_gauge!(
+        "risc_updates_queued",
+        "Number of updates queued or being handled"
+    ).expect("failed to register UPDATES_QUEUED metric");
+
+    /// The number of action invocations, by action and result.
+    pub static ref ACTION_INVOCATIONS: IntCounterVec = register_int_counter_vec!(
+        "risc_action_invocations_total",
+        "Number of action invocations, by action and result",
+        &["action", "result"]
+    ).expect("failed to register ACTION_INVOCATIONS metric");
+
+    /// The time it took to invoke an action in seconds, by action.
+    pub static ref ACTION_DURATION: HistogramVec = register_histogram_vec!(
+        "risc_action_duration_seconds",
+        "Time it took to invoke an action in seconds, by action",
+        &["action"],
+        vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0]
+    ).expect("failed to register ACTION_DURATION metric");
+
+    /// The number of panics caught while handling updates.
+    pub static ref PANICS: IntCounter = register_int_counter!(
+        "risc_panics_total",
+        "Number of panics caught while handling updates"
+    ).expect("failed to register PANICS metric");
+
+    /// The number of failed requests to send or edit a message through Telegram.
+    pub static ref TELEGRAM_SEND_ERRORS: IntCounter = register_int_counter!(
+        "risc_telegram_send_errors_total",
+        "Number of failed requests to send or edit a message through Telegram"
+    ).expect("failed to register TELEGRAM_SEND_ERRORS metric");
+
+    /// The number of flood errors received from Telegram, for exceeding rate limits.
+    pub static ref TELEGRAM_RATE_LIMITED: IntCounter = register_int_counter!(
+        "risc_telegram_rate_limited_total",
+        "Number of flood errors received from Telegram, for exceeding rate limits"
+    ).expect("failed to register TELEGRAM_RATE_LIMITED metric");
+
+    /// The number of stats entries queued to be flushed to the database.
+    pub static ref STATS_QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "risc_stats_queue_depth",
+        "Number of stats entries queued to be flushed to the database"
+    ).expect("failed to register STATS_QUEUE_DEPTH metric");
+
+    /// The time it took to flush stats to the database in seconds.
+    pub static ref STATS_FLUSH_DURATION: Histogram = register_histogram!(
+        "risc_stats_flush_duration_seconds",
+        "Time it took to flush stats to the database in seconds"
+    ).expect("failed to register STATS_FLUSH_DURATION metric");
+
+    /// The number of stats flushes that failed to flush all queued stats.
+    pub static ref STATS_FLUSH_FAILURES: IntCounter = register_int_counter!(
+        "risc_stats_flush_failures_total",
+        "Number of stats flushes that failed to flush all queued stats"
+    ).expect("failed to register STATS_FLUSH_FAILURES metric");
+
+    /// The number of commands currently running in an exec container.
+    pub static ref EXEC_RUNNING: IntGauge = register_int_gauge!(
+        "risc_exec_running",
+        "Number of commands currently running in an exec container"
+    ).expect("failed to register EXEC_RUNNING metric");
+}
+
+/// Get the metrics label for the kind of the given update.
+pub fn update_kind_label(kind: &UpdateKind) -> &'static str {
+    match kind {
+        UpdateKind::Message(_) => "message",
+        UpdateKind::EditedMessage(_) => "edited_message",
+        UpdateKind::ChannelPost(_) => "channel_post",
+        UpdateKind::EditedChannelPost(_) => "edited_channel_post",
+        UpdateKind::InlineQuery(_) => "inline_query",
+        UpdateKind::CallbackQuery(_) => "callback_query",
+        _ => "other",
+    }
+}
+
+/// Encode all registered metrics in the Prometheus text format.
+pub fn encode() -> Result<(String, Vec<u8>), prometheus::Error> {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&prometheus::gather(), &mut buffer)?;
+    Ok((encoder.format_type().to_owned(), buffer))
+}
@@ -0,0 +1,149 @@
+use std::io;
+
+use diesel::{
+    r2d2::PoolError,
+    result::{Error as DieselError, QueryResult},
+};
+use diesel_migrations::{setup_database, MigrationConnection, RunMigrationsError};
+
+use crate::config::MigrationMode;
+use crate::db::{DbPool, DbPooled};
+
+/// The schema version of the newest migration.
+///
+/// This is the version of the newest directory in `migrations/<backend>`, which is the same for
+/// all backends, determined by the build script.
+pub const SCHEMA_VERSION: &str = env!("SCHEMA_VERSION");
+
+/// Migrations embedded for the MySQL backend.
+mod mysql {
+    embed_migrations!("migrations/mysql");
+}
+
+/// Migrations embedded for the PostgreSQL backend.
+mod postgres {
+    embed_migrations!("migrations/postgres");
+}
+
+/// Migrations embedded for the SQLite backend.
+mod sqlite {
+    embed_migrations!("migrations/sqlite");
+}
+
+/// Get the current schema version of the database.
+///
+/// `None` is returned if no migration has been applied yet.
+pub fn schema_version(pool: &DbPool) -> Result<Option<String>, Error> {
+    let connection = pool.get().map_err(Error::Connect)?;
+    with_connection!(connection, |conn| latest_version(conn)).map_err(Error::Version)
+}
+
+/// Get the version of the latest migration applied through the given connection.
+///
+/// This creates the migrations table if it doesn't exist yet.
+fn latest_version<C: MigrationConnection>(conn: &C) -> QueryResult<Option<String>> {
+    setup_database(conn)?;
+    conn.latest_run_migration_version()
+}
+
+/// Apply all pending migrations to the database.
+///
+/// The name of each migration that is applied is logged.
+pub fn run(pool: &DbPool) -> Result<(), Error> {
+    let connection = pool.get().map_err(Error::Connect)?;
+    let out = &mut LogOutput::default();
+    match &connection {
+        DbPooled::Mysql(conn) => mysql::embedded_migrations::run_with_output(&**conn, out),
+        DbPooled::Postgres(conn) => postgres::embedded_migrations::run_with_output(&**conn, out),
+        DbPooled::Sqlite(conn) => sqlite::embedded_migrations::run_with_output(&**conn, out),
+    }
+    .map_err(Error::Run)
+}
+
+/// Migration output, logged line by line.
+#[derive(Default)]
+struct LogOutput {
+    /// The output of the current line, not complete yet.
+    line: Vec<u8>,
+}
+
+impl io::Write for LogOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.line.drain(..=end).collect();
+            info!("{}", String::from_utf8_lossy(&line).trim_end());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Check the database schema, and apply pending migrations if the given mode allows it.
+///
+/// Returns the resulting schema version. Fails if migrations are pending but may not be applied,
+/// or if the database schema is newer than this version of the bot supports.
+pub fn prepare(pool: &DbPool, mode: MigrationMode) -> Result<String, Error> {
+    // Check the current version, refuse databases migrated by a newer bot
+    let current = schema_version(pool)?;
+    match current.as_deref() {
+        Some(version) if version == SCHEMA_VERSION => return Ok(version.into()),
+        Some(version) if version > SCHEMA_VERSION => {
+            return Err(Error::Newer {
+                current: version.into(),
+            })
+        }
+        _ => {}
+    }
+
+    // Migrations are pending, apply them if allowed
+    if mode == MigrationMode::Check {
+        return Err(Error::Pending { current });
+    }
+    info!(
+        current = current.as_deref().unwrap_or("empty"),
+        "database schema is outdated, applying migrations",
+    );
+    run(pool)?;
+
+    Ok(schema_version(pool)?.unwrap_or_default())
+}
+
+/// A migration error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to get a database connection.
+    #[fail(display = "failed to get database connection from pool")]
+    Connect(#[cause] PoolError),
+
+    /// Failed to query the current schema version.
+    #[fail(display = "failed to query database schema version")]
+    Version(#[cause] DieselError),
+
+    /// Failed to apply migrations.
+    #[fail(display = "failed to apply database migrations")]
+    Run(#[cause] RunMigrationsError),
+
+    /// Migrations are pending, but may not be applied automatically.
+    #[fail(
+        display = "database schema is outdated ({:?}, expected {}), apply migrations with the migrate subcommand or enable automatic migrations",
+        current, SCHEMA_VERSION
+    )]
+    Pending {
+        /// The current schema version.
+        current: Option<String>,
+    },
+
+    /// The database schema is newer than supported.
+    #[fail(
+        display = "database schema version {} is newer than supported version {}, update the bot",
+        current, SCHEMA_VERSION
+    )]
+    Newer {
+        /// The current schema version.
+        current: String,
+    },
+}
@@ -1,14 +1,15 @@
-use failure::SyncFailure;
 use regex::Regex;
 use telegram_bot::{
     prelude::*,
     types::{Message, MessageChat, MessageKind, ParseMode},
-    Error as TelegramError,
 };
 
 use crate::cmd::handler::{matches_cmd, Error as CmdHandlerError, Handler as CmdHandler};
 use crate::executor::isolated;
+use crate::send::Error as SendError;
+use crate::settings::Feature;
 use crate::state::State;
+use crate::stats::TelegramToI64;
 use crate::traits::MessageText;
 
 lazy_static! {
@@ -39,38 +40,45 @@ impl Handler {
     pub async fn handle(state: State, msg: Message) -> Result<(), Error> {
         if let MessageKind::Text { ref data, .. } = &msg.kind {
             // Log all incomming text messages
-            println!(
-                "MSG <{}>@{}: {}",
-                &msg.from.first_name,
-                &msg.chat.id(),
-                data,
-            );
+            info!(from = %msg.from.first_name, text = %data, "received message");
 
             // Route the message to the command handler, if it's a command
-            if let Some(cmd) = matches_cmd(data) {
+            if let Some(cmd) = matches_cmd(data, &state.bot().username) {
                 return CmdHandler::handle(state.clone(), cmd, msg.clone())
                     .await
                     .map_err(Error::HandleCmd);
             }
 
+            // Only handle features that are enabled in this chat
+            let chat = msg.chat.id().to_i64();
+            let settings = state.settings();
+
             // Handle Reddit messages
-            if let Some(future) = Self::handle_reddit(&state, data, &msg).await {
-                return future;
+            if settings.is_enabled(&state, chat, Feature::Reddit).await {
+                if let Some(future) = Self::handle_reddit(&state, data, &msg).await {
+                    return future;
+                }
             }
 
             // Handle sed messages
-            if let Some(future) = Self::handle_sed(&state, data, &msg).await {
-                return future.map_err(Error::HandleSed);
+            if settings.is_enabled(&state, chat, Feature::Sed).await {
+                if let Some(future) = Self::handle_sed(&state, data, &msg).await {
+                    return future.map_err(Error::HandleSed);
+                }
             }
 
             // Handle tr messages
-            if let Some(future) = Self::handle_tr(&state, data, &msg).await {
-                return future.map_err(Error::HandleTr);
+            if settings.is_enabled(&state, chat, Feature::Tr).await {
+                if let Some(future) = Self::handle_tr(&state, data, &msg).await {
+                    return future.map_err(Error::HandleTr);
+                }
             }
 
             // Route private messages
             if let MessageChat::Private(..) = &msg.chat {
-                return Self::handle_private(&state, &msg).await;
+                if settings.is_enabled(&state, chat, Feature::Private).await {
+                    return Self::handle_private(&state, &msg).await;
+                }
             }
         }
 
@@ -111,14 +119,14 @@ impl Handler {
         // Send a response
         Some(
             state
-                .telegram_send(
+                .send(
                     msg.text_reply(reddits.join("\n"))
                         .parse_mode(ParseMode::Markdown)
                         .disable_notification(),
                 )
                 .await
                 .map(|_| ())
-                .map_err(|err| Error::HandleReddit(SyncFailure::new(err))),
+                .map_err(Error::HandleReddit),
         )
     }
 
@@ -156,7 +164,7 @@ impl Handler {
         let msg = msg.clone();
 
         // Run sed, gather results
-        let sed = isolated::execute_sync(cmd, None, None)
+        let sed = isolated::execute_sync(state.config(), cmd, None, None)
             .await
             .map_err(|_| SedError::Evaluate);
         let (mut output, status) = match sed {
@@ -172,10 +180,10 @@ impl Handler {
         // Send the response
         Some(
             state
-                .telegram_send(msg.text_reply(&output).disable_notification())
+                .send(msg.text_reply(&output).disable_notification())
                 .await
                 .map(|_| ())
-                .map_err(|err| SedError::Respond(SyncFailure::new(err))),
+                .map_err(SedError::Respond),
         )
     }
 
@@ -213,7 +221,7 @@ impl Handler {
         let msg = msg.clone();
 
         // Run tr, gather results
-        let tr = isolated::execute_sync(cmd, None, None).await;
+        let tr = isolated::execute_sync(state.config(), cmd, None, None).await;
         let (mut output, status) = match tr {
             Ok(tr) => (tr.0, tr.1),
             Err(_) => return Some(Err(TrError::Evaluate)),
@@ -227,10 +235,10 @@ impl Handler {
         // Send the response
         Some(
             state
-                .telegram_send(msg.text_reply(&output).disable_notification())
+                .send(msg.text_reply(&output).disable_notification())
                 .await
                 .map(|_| ())
-                .map_err(|err| TrError::Respond(SyncFailure::new(err))),
+                .map_err(TrError::Respond),
         )
     }
 
@@ -238,7 +246,7 @@ impl Handler {
     pub async fn handle_private(state: &State, msg: &Message) -> Result<(), Error> {
         // Send a message to the user
         state
-            .telegram_send(
+            .send(
                 msg.text_reply(format!(
                     "`BLEEP BLOOP`\n`I AM A BOT`\n\n{}, direct messages are not supported yet.",
                     msg.from.first_name,
@@ -247,7 +255,7 @@ impl Handler {
             )
             .await
             .map(|_| ())
-            .map_err(|err| Error::HandlePrivate(SyncFailure::new(err)))
+            .map_err(Error::HandlePrivate)
     }
 }
 
@@ -260,7 +268,7 @@ pub enum Error {
 
     /// An error occurred while processing a Reddit message.
     #[fail(display = "failed to process reddit message")]
-    HandleReddit(#[cause] SyncFailure<TelegramError>),
+    HandleReddit(#[cause] SendError),
 
     /// An error occurred while evaluating the sed expression.
     #[fail(display = "failed to process sed expression")]
@@ -272,7 +280,11 @@ pub enum Error {
 
     /// An error occurred while processing a private message.
     #[fail(display = "failed to process private message")]
-    HandlePrivate(#[cause] SyncFailure<TelegramError>),
+    HandlePrivate(#[cause] SendError),
+
+    /// The message handler panicked.
+    #[fail(display = "message handler panicked: {}", _0)]
+    Panic(String),
 }
 
 impl From<CmdHandlerError> for Error {
@@ -302,7 +314,7 @@ pub enum SedError {
 
     /// Failed to send the response message
     #[fail(display = "failed to send sed response")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
 }
 
 /// A message handler error.
@@ -314,5 +326,204 @@ pub enum TrError {
 
     /// Failed to send the response message
     #[fail(display = "failed to send tr response")]
-    Respond(#[cause] SyncFailure<TelegramError>),
+    Respond(#[cause] SendError),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::cmd::permission::Role;
+    use crate::config::Config;
+    use crate::stats::TelegramToI64;
+    use crate::testing::{self, Harness, GROUP_ID, PRIVATE_ID};
+
+    #[tokio::test]
+    async fn command_replies_to_message() {
+        let harness = Harness::new().await;
+        let msg = testing::message(GROUP_ID, "/ping");
+        let id = msg.id.to_i64();
+        harness.handle(msg).await.unwrap();
+
+        let sent = harness.transport.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].body["chat_id"], GROUP_ID);
+        assert_eq!(sent[0].body["reply_to_message_id"], id);
+        assert_eq!(sent[0].body["text"], "Pong!");
+    }
+
+    #[tokio::test]
+    async fn command_addressed_to_other_bot_is_ignored() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/ping@otherbot"))
+            .await
+            .unwrap();
+        assert!(harness.transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn command_fails_if_reply_is_refused() {
+        let harness = Harness::new().await;
+        harness
+            .transport
+            .script_error("sendMessage", 400, "Bad Request: chat not found");
+        assert!(harness
+            .handle(testing::message(GROUP_ID, "/ping"))
+            .await
+            .is_err());
+        assert_eq!(harness.transport.sent().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn command_fails_if_bot_is_forbidden() {
+        let harness = Harness::new().await;
+        harness
+            .transport
+            .script_error("sendMessage", 403, "Forbidden: bot was kicked");
+        assert!(harness
+            .handle(testing::message(GROUP_ID, "/ping"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn command_without_arguments_ignores_input() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/ping are you there?"))
+            .await
+            .unwrap();
+        assert_eq!(harness.sent_texts(), vec!["Pong!"]);
+    }
+
+    #[tokio::test]
+    async fn command_with_invalid_arguments_replies_usage() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/echo"))
+            .await
+            .unwrap();
+
+        let texts = harness.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(
+            texts[0].starts_with("Missing argument: text"),
+            "{}",
+            texts[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn command_takes_unbalanced_quotes_as_is() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/echo \"hello"))
+            .await
+            .unwrap();
+        assert_eq!(harness.sent_texts(), vec!["\"hello"]);
+    }
+
+    #[tokio::test]
+    async fn command_is_refused_during_cooldown() {
+        let harness = Harness::new().await;
+        for _ in 0..4 {
+            harness
+                .handle(testing::message(GROUP_ID, "/stats"))
+                .await
+                .unwrap();
+        }
+
+        // Two stats are sent, the user is told to slow down once
+        let texts = harness.sent_texts();
+        assert_eq!(texts.len(), 3);
+        assert!(texts[2].starts_with("Please slow down"), "{}", texts[2]);
+    }
+
+    #[tokio::test]
+    async fn restricted_commands_are_refused_by_default() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/exec echo hi"))
+            .await
+            .unwrap();
+
+        // The test user is not among the administrators of the group
+        harness.transport.script_result(
+            "getChatAdministrators",
+            json!([{
+                "user": { "id": 1, "is_bot": false, "first_name": "Admin" },
+                "status": "creator",
+            }]),
+        );
+        harness
+            .handle(testing::message(GROUP_ID, "/all"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            harness.sent_texts(),
+            vec![
+                "Sorry, /exec is only available to allowlisted users.",
+                "Sorry, /all is only available to chat admins.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn command_role_can_be_configured() {
+        let mut config = Config::default();
+        config.bot.roles.insert("ping".into(), Role::Allowlisted);
+        let harness = Harness::with_config(config).await;
+        harness
+            .handle(testing::message(GROUP_ID, "/ping"))
+            .await
+            .unwrap();
+        assert_eq!(
+            harness.sent_texts(),
+            vec!["Sorry, /ping is only available to allowlisted users."]
+        );
+
+        // Owners may use every command
+        let mut config = Config::default();
+        config.bot.roles.insert("ping".into(), Role::Owner);
+        config.bot.owners.push(testing::USER_ID);
+        let harness = Harness::with_config(config).await;
+        harness
+            .handle(testing::message(GROUP_ID, "/ping"))
+            .await
+            .unwrap();
+        assert_eq!(harness.sent_texts(), vec!["Pong!"]);
+    }
+
+    #[tokio::test]
+    async fn command_uses_replied_message() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::reply(GROUP_ID, "/rt", "Hello world"))
+            .await
+            .unwrap();
+
+        let texts = harness.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].ends_with("<b>RTs:</b> Hello world"));
+    }
+
+    #[tokio::test]
+    async fn start_is_only_answered_in_private() {
+        let harness = Harness::new().await;
+        harness
+            .handle(testing::message(GROUP_ID, "/start"))
+            .await
+            .unwrap();
+        assert!(harness.transport.sent().is_empty());
+
+        harness
+            .handle(testing::message(PRIVATE_ID, "/start"))
+            .await
+            .unwrap();
+        let texts = harness.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].starts_with("*Welcome Tester!*"));
+    }
 }
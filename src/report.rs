@@ -0,0 +1,93 @@
+use std::collections::hash_map::{DefaultHasher, HashMap, RandomState};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+use crate::config::ErrorsConfig;
+
+/// Decides whether errors are replied to users, to not spam a chat during an outage.
+///
+/// Each chat gets at most one error reply per cooldown, and the same error is replied only once
+/// per deduplication window.
+pub struct ErrorReporter {
+    /// The error reply state of each chat, by chat ID.
+    chats: Mutex<HashMap<i64, ChatErrors>>,
+
+    /// Counter to make correlation IDs unique.
+    counter: AtomicUsize,
+
+    /// Random state to derive correlation IDs with.
+    random: RandomState,
+}
+
+/// The error reply state of a chat.
+struct ChatErrors {
+    /// The time an error was last replied in this chat.
+    replied_at: Instant,
+
+    /// The time each recent error was replied in this chat, by error fingerprint.
+    recent: HashMap<u64, Instant>,
+}
+
+impl ErrorReporter {
+    /// Construct a new error reporter, no errors are replied yet.
+    pub fn new() -> Self {
+        ErrorReporter {
+            chats: Mutex::new(HashMap::new()),
+            counter: AtomicUsize::new(0),
+            random: RandomState::new(),
+        }
+    }
+
+    /// Generate a short unique ID to correlate an error reply with the logs.
+    pub fn correlation_id(&self) -> String {
+        let mut hasher = self.random.build_hasher();
+        self.counter
+            .fetch_add(1, Ordering::Relaxed)
+            .hash(&mut hasher);
+        SystemTime::now().hash(&mut hasher);
+        format!("{:08x}", hasher.finish() as u32)
+    }
+
+    /// Check whether the given error should be replied in the given chat, and record it if so.
+    ///
+    /// The error is identified by its formatted cause chain.
+    pub fn should_reply(&self, config: &ErrorsConfig, chat: i64, error: &str) -> bool {
+        let now = Instant::now();
+        let fingerprint = fingerprint(error);
+        let mut chats = self.chats.lock().unwrap();
+
+        // Forget errors that are outside the windows
+        chats.retain(|_, errors| {
+            errors
+                .recent
+                .retain(|_, at| now.duration_since(*at) < config.dedup_window());
+            now.duration_since(errors.replied_at) < config.cooldown() || !errors.recent.is_empty()
+        });
+
+        // Do not reply within the cooldown or to a recent duplicate
+        if let Some(errors) = chats.get(&chat) {
+            if now.duration_since(errors.replied_at) < config.cooldown()
+                || errors.recent.contains_key(&fingerprint)
+            {
+                return false;
+            }
+        }
+
+        let errors = chats.entry(chat).or_insert_with(|| ChatErrors {
+            replied_at: now,
+            recent: HashMap::new(),
+        });
+        errors.replied_at = now;
+        errors.recent.insert(fingerprint, now);
+        true
+    }
+}
+
+/// Get a fingerprint for the given formatted error, to recognize duplicates by.
+fn fingerprint(error: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    error.hash(&mut hasher);
+    hasher.finish()
+}
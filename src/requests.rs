@@ -0,0 +1,146 @@
+use serde::Serialize;
+use telegram_bot::types::{
+    Error as RequestError, HttpRequest, JsonRequestType, JsonTrueToUnitResponse, Request,
+    RequestType, RequestUrl,
+};
+
+/// Register a webhook to receive updates on.
+///
+/// See: <https://core.telegram.org/bots/api#setwebhook>
+#[derive(Serialize, Debug, Clone)]
+#[must_use = "requests do nothing unless sent"]
+pub struct SetWebhook {
+    /// The HTTPS URL to send updates to.
+    url: String,
+
+    /// A secret token sent along with each webhook request in a header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_token: Option<String>,
+}
+
+impl SetWebhook {
+    /// Construct a request to register the given webhook URL.
+    pub fn new(url: String, secret_token: Option<String>) -> Self {
+        SetWebhook { url, secret_token }
+    }
+}
+
+impl Request for SetWebhook {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<HttpRequest, RequestError> {
+        Self::Type::serialize(RequestUrl::method("setWebhook"), self)
+    }
+}
+
+/// Remove the registered webhook, to receive updates by polling instead.
+///
+/// See: <https://core.telegram.org/bots/api#deletewebhook>
+#[derive(Serialize, Debug, Clone)]
+#[must_use = "requests do nothing unless sent"]
+pub struct DeleteWebhook {
+    /// Whether to drop all pending updates.
+    drop_pending_updates: bool,
+}
+
+impl DeleteWebhook {
+    /// Construct a request to remove the webhook, keeping pending updates.
+    pub fn new() -> Self {
+        DeleteWebhook {
+            drop_pending_updates: false,
+        }
+    }
+}
+
+impl Request for DeleteWebhook {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<HttpRequest, RequestError> {
+        Self::Type::serialize(RequestUrl::method("deleteWebhook"), self)
+    }
+}
+
+/// Set the list of commands shown to users, for the given scope and language.
+///
+/// See: <https://core.telegram.org/bots/api#setmycommands>
+#[derive(Serialize, Debug, Clone)]
+#[must_use = "requests do nothing unless sent"]
+pub struct SetMyCommands {
+    /// The commands to show.
+    commands: Vec<BotCommand>,
+
+    /// The users the commands are shown to.
+    scope: BotCommandScope,
+
+    /// The two-letter ISO 639-1 language code of the users the commands are shown to, all users
+    /// without a dedicated list if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language_code: Option<String>,
+}
+
+impl SetMyCommands {
+    /// Construct a request to set the given commands, for the given scope and language.
+    pub fn new(
+        commands: Vec<BotCommand>,
+        scope: BotCommandScope,
+        language_code: Option<String>,
+    ) -> Self {
+        SetMyCommands {
+            commands,
+            scope,
+            language_code,
+        }
+    }
+}
+
+impl Request for SetMyCommands {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<HttpRequest, RequestError> {
+        Self::Type::serialize(RequestUrl::method("setMyCommands"), self)
+    }
+}
+
+/// A command shown to users.
+///
+/// See: <https://core.telegram.org/bots/api#botcommand>
+#[derive(Serialize, Debug, Clone)]
+pub struct BotCommand {
+    /// The command name, without `/` prefix.
+    command: String,
+
+    /// The description of the command.
+    description: String,
+}
+
+impl BotCommand {
+    /// Construct a command with the given name and description.
+    pub fn new(command: String, description: String) -> Self {
+        BotCommand {
+            command,
+            description,
+        }
+    }
+}
+
+/// The users a command list is shown to.
+///
+/// See: <https://core.telegram.org/bots/api#botcommandscope>
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotCommandScope {
+    /// All users, unless a narrower scope applies.
+    Default,
+
+    /// All private chats.
+    AllPrivateChats,
+
+    /// All group and supergroup chats.
+    AllGroupChats,
+
+    /// All administrators of group and supergroup chats.
+    AllChatAdministrators,
+}
@@ -2,8 +2,26 @@ table! {
     chat (telegram_id) {
         telegram_id -> Bigint,
         title -> Nullable<Varchar>,
-        created_at -> Datetime,
-        updated_at -> Datetime,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    chat_alias (chat_id, alias) {
+        chat_id -> Bigint,
+        alias -> Varchar,
+        command -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    chat_settings (chat_id, feature) {
+        chat_id -> Bigint,
+        feature -> Varchar,
+        enabled -> Bool,
+        updated_at -> Timestamp,
     }
 }
 
@@ -14,8 +32,16 @@ table! {
         message_type -> Smallint,
         messages -> Integer,
         edits -> Integer,
-        created_at -> Datetime,
-        updated_at -> Datetime,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    handled_update (update_id) {
+        update_id -> Bigint,
+        handled_at -> Timestamp,
+        handled -> Bool,
     }
 }
 
@@ -25,8 +51,8 @@ table! {
         username -> Nullable<Text>,
         first_name -> Text,
         last_name -> Nullable<Text>,
-        created_at -> Datetime,
-        updated_at -> Datetime,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -35,6 +61,9 @@ joinable!(chat_user_stats -> user (user_id));
 
 allow_tables_to_appear_in_same_query!(
     chat,
+    chat_alias,
+    chat_settings,
     chat_user_stats,
+    handled_update,
     user,
 );
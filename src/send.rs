@@ -0,0 +1,179 @@
+use std::fmt;
+use std::time::Duration;
+
+use failure::SyncFailure;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use telegram_bot::{
+    types::{
+        Body, ChatId, Error as RequestError, HttpRequest, HttpResponse, Message,
+        MessageOrChannelPost,
+    },
+    Error as TelegramError,
+};
+
+use crate::stats::{Stats, TelegramToI64};
+use crate::transport;
+
+/// The outcome of sending a request that responds with a message to Telegram.
+///
+/// Besides a sent message, this covers the ways Telegram may refuse a request that callers could
+/// want to react to. Other failures are reported as `Error`.
+#[derive(Debug)]
+pub enum SendOutcome<M> {
+    /// The message was sent or edited.
+    Sent(M),
+
+    /// Telegram refused the request because rate limits were exceeded, even after retrying.
+    RateLimited { retry_after: Duration },
+
+    /// The bot is not allowed to send to the chat, because it was blocked or removed from it.
+    Forbidden { description: String },
+
+    /// The group was upgraded to a supergroup, requests must be sent to the new chat instead.
+    ChatMigrated { to: ChatId },
+}
+
+impl<M> SendOutcome<M> {
+    /// Get the sent message, or an error if the message was not sent.
+    pub fn into_sent(self) -> Result<M, Error> {
+        match self {
+            SendOutcome::Sent(msg) => Ok(msg),
+            outcome => Err(Error::NotSent(outcome.to_string())),
+        }
+    }
+
+    /// Classify a failed response of Telegram into an outcome, if it is one callers may react to.
+    ///
+    /// Returns `None` if the request succeeded, or failed for any other reason.
+    pub fn from_response(response: &HttpResponse) -> Option<Self> {
+        let body = response_body(response)?;
+        if body["ok"].as_bool() != Some(false) {
+            return None;
+        }
+
+        match body["error_code"].as_u64()? {
+            429 => Some(SendOutcome::RateLimited {
+                retry_after: Duration::from_secs(
+                    body["parameters"]["retry_after"].as_u64().unwrap_or(1),
+                ),
+            }),
+            403 => Some(SendOutcome::Forbidden {
+                description: body["description"].as_str().unwrap_or_default().to_owned(),
+            }),
+            _ => body["parameters"]["migrate_to_chat_id"].as_i64().map(|to| {
+                SendOutcome::ChatMigrated {
+                    to: ChatId::new(to),
+                }
+            }),
+        }
+    }
+}
+
+impl<M> fmt::Display for SendOutcome<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendOutcome::Sent(_) => write!(f, "sent"),
+            SendOutcome::RateLimited { retry_after } => write!(
+                f,
+                "rate limited by Telegram, retry after {}s",
+                retry_after.as_secs()
+            ),
+            SendOutcome::Forbidden { description } => {
+                write!(f, "forbidden by Telegram: {}", description)
+            }
+            SendOutcome::ChatMigrated { to } => write!(f, "chat migrated to {}", to),
+        }
+    }
+}
+
+/// A message the bot may get as response after sending a request to Telegram.
+pub trait SentMessage: DeserializeOwned + Send + 'static {
+    /// Track this message in the stats, as sent or edited by the bot.
+    fn track(&self, stats: &Stats);
+}
+
+impl SentMessage for Message {
+    fn track(&self, stats: &Stats) {
+        if self.edit_date.is_none() {
+            stats.increase_message_stats(self, 1, 0);
+        } else {
+            stats.increase_message_stats(self, 0, 1);
+        }
+    }
+}
+
+impl SentMessage for MessageOrChannelPost {
+    fn track(&self, stats: &Stats) {
+        let edit_date = match self {
+            MessageOrChannelPost::Message(msg) => msg.edit_date,
+            MessageOrChannelPost::ChannelPost(post) => post.edit_date,
+        };
+
+        if edit_date.is_none() {
+            stats.increase_message_or_channel_post_stats(self, 1, 0);
+        } else {
+            stats.increase_message_or_channel_post_stats(self, 0, 1);
+        }
+    }
+}
+
+/// Check whether the given response is a transient Telegram server error, worth retrying.
+pub fn is_server_error(response: &HttpResponse) -> bool {
+    response_body(response)
+        .and_then(|body| body["error_code"].as_u64())
+        .map_or(false, |code| code >= 500)
+}
+
+/// Retarget a request sending a message to the supergroup its group was upgraded to.
+///
+/// Returns the chat the request was sent to and the retargeted request, or `None` if the request
+/// can't be resent, such as requests editing messages in the old chat.
+pub fn migrate_request(request: &HttpRequest, to: ChatId) -> Option<(ChatId, HttpRequest)> {
+    if !transport::method_name(request).starts_with("send") {
+        return None;
+    }
+    let mut body: Value = match request.body {
+        Body::Json(ref json) => serde_json::from_str(json).ok()?,
+        _ => return None,
+    };
+    let from = ChatId::new(body["chat_id"].as_i64()?);
+
+    // Messages in the old chat can't be replied to from the new chat
+    let fields = body.as_object_mut()?;
+    fields.insert("chat_id".into(), to.to_i64().into());
+    fields.remove("reply_to_message_id");
+
+    let mut migrated = request.clone();
+    migrated.body = Body::Json(body.to_string());
+    Some((from, migrated))
+}
+
+/// Parse the JSON body of the given response.
+fn response_body(response: &HttpResponse) -> Option<Value> {
+    serde_json::from_slice(response.body.as_ref()?).ok()
+}
+
+/// A send error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to serialize the request.
+    #[fail(display = "failed to serialize Telegram request")]
+    Serialize(#[cause] RequestError),
+
+    /// Failed to reach the Telegram API, also after retrying.
+    #[fail(display = "failed to reach Telegram API")]
+    Network(#[cause] SyncFailure<TelegramError>),
+
+    /// The Telegram API did not respond in time.
+    #[fail(display = "Telegram API request timed out")]
+    Timeout,
+
+    /// The Telegram API responded with an error.
+    #[fail(display = "Telegram API returned an error")]
+    Api(#[cause] RequestError),
+
+    /// The message was not sent, as Telegram refused the request.
+    #[fail(display = "message not sent, {}", _0)]
+    NotSent(String),
+}
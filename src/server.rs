@@ -0,0 +1,97 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Error as HyperError, Method, Request, Response, Server, StatusCode,
+};
+use tokio::runtime::Handle;
+
+use crate::metrics;
+use crate::state::State;
+
+/// Start the operations HTTP server, if enabled.
+///
+/// The server is spawned on the given runtime, and exposes Prometheus metrics on `/metrics` and
+/// a JSON health report on `/health`. The health endpoint responds with `503` if unhealthy, to
+/// use it as liveness or readiness probe.
+pub fn spawn(state: &State, handle: &Handle) -> Result<(), Error> {
+    let config = &state.config().server;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    // Bind the server, and spawn it on the runtime
+    let state = state.clone();
+    let server = Server::try_bind(&config.listen)
+        .map_err(|err| Error::Bind(config.listen, err))?
+        .serve(make_service_fn(move |_| {
+            let state = state.clone();
+            let service = service_fn(move |req| handle_request(state.clone(), req));
+            async move { Ok::<_, Infallible>(service) }
+        }));
+    handle.spawn(async move {
+        if let Err(err) = server.await {
+            error!(%err, "operations server failed");
+        }
+    });
+
+    info!(listen = %config.listen, "serving metrics and health checks");
+
+    Ok(())
+}
+
+/// Handle a single request to the operations server.
+async fn handle_request(state: State, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => Ok(respond_metrics()),
+        (&Method::GET, "/health") => Ok(respond_health(&state).await),
+        (_, "/metrics") | (_, "/health") => Ok(respond(StatusCode::METHOD_NOT_ALLOWED)),
+        _ => Ok(respond(StatusCode::NOT_FOUND)),
+    }
+}
+
+/// Build a response with a JSON health report.
+async fn respond_health(state: &State) -> Response<Body> {
+    let report = state.health().check(state).await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(&report).unwrap()))
+        .unwrap()
+}
+
+/// Build a response with all metrics in the Prometheus text format.
+fn respond_metrics() -> Response<Body> {
+    match metrics::encode() {
+        Ok((content_type, body)) => Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap(),
+        Err(err) => {
+            error!(%err, "failed to encode metrics");
+            respond(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Build an empty response with the given status code.
+fn respond(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// An operations server error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to bind the server to the configured address.
+    #[fail(display = "failed to bind operations server to {}", _0)]
+    Bind(SocketAddr, #[cause] HyperError),
+}
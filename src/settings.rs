@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+
+use crate::cmd::action::ACTIONS;
+use crate::db::QueryError;
+use crate::schema::chat_settings;
+use crate::state::State;
+
+/// The command of the settings action, which can't be disabled to not lock a chat out.
+const SETTINGS_CMD: &str = "settings";
+
+/// The time to cache the settings of a chat, before loading them from the database again.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A feature that can be enabled or disabled per chat.
+///
+/// All features are enabled by default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Linking Reddit references, such as `/r/rust`.
+    Reddit,
+
+    /// Replacing text in a replied message with sed syntax.
+    Sed,
+
+    /// Translating characters in a replied message with tr syntax.
+    Tr,
+
+    /// Replying to private messages that aren't handled otherwise.
+    Private,
+
+    /// Invoking the action with the given command name.
+    Action(&'static str),
+}
+
+impl Feature {
+    /// List all features that can be toggled, including all visible actions.
+    pub fn all() -> Vec<Feature> {
+        let mut features = vec![Feature::Reddit, Feature::Sed, Feature::Tr, Feature::Private];
+        features.extend(
+            ACTIONS
+                .iter()
+                .filter(|action| !action.hidden() && action.cmd() != SETTINGS_CMD)
+                .map(|action| Feature::Action(action.cmd())),
+        );
+        features
+    }
+
+    /// Find the feature with the given key.
+    pub fn from_key(key: &str) -> Option<Feature> {
+        Self::all().into_iter().find(|feature| feature.key() == key)
+    }
+
+    /// The key the feature is stored by, such as `reddit` or `/exec` for actions.
+    pub fn key(&self) -> String {
+        match self {
+            Feature::Reddit => "reddit".into(),
+            Feature::Sed => "sed".into(),
+            Feature::Tr => "tr".into(),
+            Feature::Private => "private".into(),
+            Feature::Action(cmd) => format!("/{}", cmd),
+        }
+    }
+
+    /// A short label to show the feature by.
+    pub fn label(&self) -> String {
+        match self {
+            Feature::Reddit => "Reddit links".into(),
+            Feature::Sed => "sed".into(),
+            Feature::Tr => "tr".into(),
+            Feature::Private => "Private replies".into(),
+            Feature::Action(cmd) => format!("/{}", cmd),
+        }
+    }
+}
+
+/// The feature settings of a chat.
+#[derive(Clone, Debug, Default)]
+pub struct ChatSettings {
+    /// Whether each toggled feature is enabled, by feature key.
+    features: HashMap<String, bool>,
+}
+
+impl ChatSettings {
+    /// Check whether the given feature is enabled.
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.features.get(&feature.key()).copied().unwrap_or(true)
+    }
+}
+
+/// The feature settings of all chats, loaded from the database and cached.
+///
+/// Settings are cached for a short time, and expired settings are dropped when loading others.
+/// Settings toggled through another instance sharing the database may be stale until then.
+pub struct Settings {
+    /// The settings of each loaded chat along with the time they were loaded, by chat ID.
+    chats: Mutex<HashMap<i64, (Instant, ChatSettings)>>,
+}
+
+impl Settings {
+    /// Construct a new settings cache, without any chats loaded yet.
+    pub fn new() -> Self {
+        Settings {
+            chats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the settings of the given chat, load them from the database if not cached.
+    pub async fn chat(&self, state: &State, chat: i64) -> Result<ChatSettings, QueryError> {
+        let cached = self
+            .chats
+            .lock()
+            .unwrap()
+            .get(&chat)
+            .filter(|(loaded_at, _)| loaded_at.elapsed() < CACHE_TTL)
+            .map(|(_, settings)| settings.clone());
+        if let Some(settings) = cached {
+            return Ok(settings);
+        }
+
+        let features = state
+            .db_query(move |connection| {
+                with_connection!(connection, |conn| chat_settings::table
+                    .filter(chat_settings::chat_id.eq(chat))
+                    .select((chat_settings::feature, chat_settings::enabled))
+                    .load::<(String, bool)>(conn))
+            })
+            .await?;
+        let settings = ChatSettings {
+            features: features.into_iter().collect(),
+        };
+
+        // Cache the settings, drop expired ones to bound the cache to recently active chats
+        let mut chats = self.chats.lock().unwrap();
+        chats.retain(|_, (loaded_at, _)| loaded_at.elapsed() < CACHE_TTL);
+        chats.insert(chat, (Instant::now(), settings.clone()));
+        Ok(settings)
+    }
+
+    /// Check whether the given feature is enabled in the given chat.
+    ///
+    /// If the settings can't be loaded, the feature is assumed to be enabled.
+    pub async fn is_enabled(&self, state: &State, chat: i64, feature: Feature) -> bool {
+        match self.chat(state, chat).await {
+            Ok(settings) => settings.is_enabled(feature),
+            Err(err) => {
+                warn!(%err, "failed to load chat settings, assuming feature is enabled");
+                true
+            }
+        }
+    }
+
+    /// Toggle the given feature in the given chat, and get the updated settings.
+    ///
+    /// The updated settings are reloaded from the database, to include all other overrides.
+    pub async fn toggle(
+        &self,
+        state: &State,
+        chat: i64,
+        feature: Feature,
+    ) -> Result<ChatSettings, QueryError> {
+        let enabled = !self.chat(state, chat).await?.is_enabled(feature);
+        let stored = feature.key();
+        state
+            .db_query(move |connection| {
+                with_connection!(connection, |conn| conn.transaction(|| {
+                    diesel::delete(
+                        chat_settings::table
+                            .filter(chat_settings::chat_id.eq(chat))
+                            .filter(chat_settings::feature.eq(&stored)),
+                    )
+                    .execute(conn)?;
+                    diesel::insert_into(chat_settings::table)
+                        .values((
+                            chat_settings::chat_id.eq(chat),
+                            chat_settings::feature.eq(&stored),
+                            chat_settings::enabled.eq(enabled),
+                        ))
+                        .execute(conn)
+                }))
+            })
+            .await?;
+
+        self.chats.lock().unwrap().remove(&chat);
+        self.chat(state, chat).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Feature, Settings};
+    use crate::testing::{Harness, GROUP_ID};
+
+    #[tokio::test]
+    async fn toggles_are_stored() {
+        let harness = Harness::new().await;
+        let state = &harness.state;
+        let settings = state.settings();
+        assert!(settings.is_enabled(state, GROUP_ID, Feature::Sed).await);
+
+        let toggled = settings
+            .toggle(state, GROUP_ID, Feature::Sed)
+            .await
+            .unwrap();
+        assert!(!toggled.is_enabled(Feature::Sed));
+        assert!(!settings.is_enabled(state, GROUP_ID, Feature::Sed).await);
+
+        // Another instance loads the toggled settings from the database
+        let other = Settings::new();
+        assert!(!other.is_enabled(state, GROUP_ID, Feature::Sed).await);
+        assert!(other.is_enabled(state, GROUP_ID, Feature::Tr).await);
+    }
+
+    #[tokio::test]
+    async fn toggles_keep_other_overrides() {
+        let harness = Harness::new().await;
+        let state = &harness.state;
+        let settings = state.settings();
+        settings
+            .toggle(state, GROUP_ID, Feature::Sed)
+            .await
+            .unwrap();
+
+        // The cached settings expired in the meantime
+        settings.chats.lock().unwrap().clear();
+        let toggled = settings.toggle(state, GROUP_ID, Feature::Tr).await.unwrap();
+        assert!(!toggled.is_enabled(Feature::Sed));
+        assert!(!toggled.is_enabled(Feature::Tr));
+    }
+}
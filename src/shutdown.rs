@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::signal::{
+    ctrl_c,
+    unix::{signal, SignalKind},
+};
+use tokio::sync::{watch, Notify};
+use tokio::time;
+
+/// Coordinates a graceful shutdown.
+///
+/// Tasks that should complete before quitting are tracked, so they can be drained once shutdown
+/// is triggered. Long running tasks may watch for the trigger to wrap up early.
+///
+/// Clones share the same state.
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+/// The shared shutdown state.
+struct Inner {
+    /// Sender to trigger shutdown with.
+    trigger: watch::Sender<bool>,
+
+    /// Receiver to watch for the shutdown trigger, cloned for each watcher.
+    triggered: watch::Receiver<bool>,
+
+    /// The number of tracked tasks that are still running.
+    tasks: AtomicUsize,
+
+    /// Notified when the last tracked task completes.
+    idle: Notify,
+}
+
+impl Shutdown {
+    /// Construct a new shutdown coordinator, without any tracked tasks.
+    pub fn new() -> Self {
+        let (trigger, triggered) = watch::channel(false);
+        Shutdown {
+            inner: Arc::new(Inner {
+                trigger,
+                triggered,
+                tasks: AtomicUsize::new(0),
+                idle: Notify::new(),
+            }),
+        }
+    }
+
+    /// Trigger shutdown, notifying all tasks watching for it.
+    pub fn trigger(&self) {
+        let _ = self.inner.trigger.send(true);
+    }
+
+    /// Check whether shutdown has been triggered.
+    pub fn is_triggered(&self) -> bool {
+        *self.inner.triggered.borrow()
+    }
+
+    /// Wait until shutdown is triggered.
+    pub async fn triggered(&self) {
+        let mut triggered = self.inner.triggered.clone();
+        while !*triggered.borrow() {
+            if triggered.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Track the given task, so shutdown waits for it to complete.
+    ///
+    /// The task is counted as running until the returned future completes or is dropped.
+    pub fn track<F: Future>(&self, task: F) -> impl Future<Output = F::Output> {
+        self.inner.tasks.fetch_add(1, Ordering::SeqCst);
+        let guard = TaskGuard(self.inner.clone());
+        async move {
+            let output = task.await;
+            drop(guard);
+            output
+        }
+    }
+
+    /// The number of tracked tasks that are still running.
+    pub fn running(&self) -> usize {
+        self.inner.tasks.load(Ordering::SeqCst)
+    }
+
+    /// Wait for all tracked tasks to complete, at most for the given timeout.
+    ///
+    /// Returns `true` if all tasks completed, `false` if the timeout was reached.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        time::timeout(timeout, async {
+            while self.running() > 0 {
+                self.inner.idle.notified().await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+/// Guard counting a tracked task as running, until it is dropped.
+struct TaskGuard(Arc<Inner>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.0.tasks.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_one();
+        }
+    }
+}
+
+/// Wait for a signal to quit, being either `SIGINT` or `SIGTERM`.
+pub async fn signal_quit() {
+    let mut terminate =
+        signal(SignalKind::terminate()).expect("failed to register SIGTERM signal handler");
+    tokio::select! {
+        _ = ctrl_c() => info!("received SIGINT signal, preparing to quit"),
+        _ = terminate.recv() => info!("received SIGTERM signal, preparing to quit"),
+    }
+}
@@ -1,29 +1,43 @@
 use std::env;
+use std::process;
 use std::sync::Arc;
 use std::time::Duration;
 
-use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
-use diesel::{mysql::MysqlConnection, prelude::*};
+use diesel::result::QueryResult;
+use failure::SyncFailure;
 use futures::prelude::*;
 use telegram_bot::{
-    types::{JsonIdResponse, Message, MessageOrChannelPost, Request},
-    Api, Error as TelegramError,
+    types::{ChatId, GetMe, HttpRequest, JsonIdResponse, Request, ResponseType, UserId},
+    Api,
 };
-use tokio::runtime::Handle;
-
+use tokio::{runtime::Handle, task, time};
+use tracing::Instrument;
+
+use crate::alert::{AlertKind, Alerts};
+use crate::cmd::cooldown::Cooldowns;
+use crate::cmd::permission::Permissions;
+use crate::config::{Config, TransportKind};
+use crate::db::{DbPool, DbPooled, QueryError};
+use crate::health::Health;
+use crate::metrics;
+use crate::migrations::{self, Error as MigrationError};
+use crate::report::ErrorReporter;
+use crate::send::{self, Error as SendError, SendOutcome, SentMessage};
+use crate::settings::Settings;
+use crate::shutdown::Shutdown;
 use crate::stats::Stats;
+use crate::transport::{self, SharedTransport, Transport};
+use crate::updates::InFlight;
+use crate::util::print_error;
 
-/// Database connection type.
-pub type DbConnection = MysqlConnection;
-
-/// Database connection manager type.
-pub type DbConnectionManager = ConnectionManager<DbConnection>;
+/// The timeout for sending a Telegram request, including time spent waiting for rate limits.
+const SEND_TIMEOUT: Duration = Duration::from_secs(120);
 
-/// Database pool type.
-pub type DbPool = Pool<DbConnectionManager>;
+/// The number of times to retry sending a Telegram request on transient errors.
+const SEND_RETRIES: u32 = 3;
 
-/// Database pooled connection type.
-pub type DbPooled = PooledConnection<DbConnectionManager>;
+/// The time to wait before retrying to send a Telegram request, doubled on each retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
 
 /// The global application state.
 #[derive(Clone)]
@@ -31,6 +45,12 @@ pub struct State {
     /// The Telegram API client beign used.
     telegram_client: Api,
 
+    /// The transport Telegram API requests are sent through.
+    transport: SharedTransport,
+
+    /// The Telegram bot token.
+    token: Arc<String>,
+
     /// The inner state.
     inner: Arc<StateInner>,
 }
@@ -40,23 +60,73 @@ impl State {
     ///
     /// This initializes the global state.
     /// Internally this creates the Telegram API client and sets up a connection,
-    /// connects to the bot database and more.
+    /// fetches the bot identity, connects to the bot database and more.
+    ///
+    /// The loaded configuration and a handle to the Tokio runtime must be given.
+    /// Telegram API requests are sent through the configured transport.
+    pub async fn init(config: Config, handle: Handle) -> State {
+        let transport = transport::build(&config);
+        let db = StateInner::connection_pool();
+        Self::init_with_transport(config, handle, transport, db).await
+    }
+
+    /// Initialize, sending all Telegram API requests through the given transport and using the
+    /// given database.
     ///
-    /// A handle to the Tokio runtime must be given.
-    pub fn init(handle: Handle) -> State {
+    /// This allows to run the bot against a fake Telegram API.
+    pub async fn init_with_transport(
+        config: Config,
+        handle: Handle,
+        transport: Box<dyn Transport>,
+        db: DbPool,
+    ) -> State {
+        let token = Self::bot_token(&config);
+        let transport = SharedTransport::new(transport);
+        let telegram_client = Api::with_connector(token.clone(), Box::new(transport.clone()));
+        let bot = Self::fetch_bot_identity(&telegram_client).await;
+        info!(bot = %bot.username, "fetched bot identity");
         State {
-            telegram_client: Self::create_telegram_client(),
-            inner: Arc::new(StateInner::init(handle)),
+            telegram_client,
+            transport,
+            token: Arc::new(token),
+            inner: Arc::new(StateInner::init(config, handle, bot, db)),
+        }
+    }
+
+    /// Retrieve the Telegram bot token, which is not required for a fake API.
+    fn bot_token(config: &Config) -> String {
+        match config.telegram.transport {
+            TransportKind::Api => {
+                env::var("TELEGRAM_BOT_TOKEN").expect("env var TELEGRAM_BOT_TOKEN not set")
+            }
+            TransportKind::Fake => env::var("TELEGRAM_BOT_TOKEN").unwrap_or_default(),
+        }
+    }
+
+    /// Fetch the identity of the bot through the given Telegram API client.
+    async fn fetch_bot_identity(client: &Api) -> BotIdentity {
+        let me = client
+            .send(GetMe)
+            .await
+            .expect("failed to fetch bot identity from Telegram API");
+
+        BotIdentity {
+            id: me.id,
+            username: me
+                .username
+                .expect("failed to fetch bot identity, Telegram API returned no bot username"),
+            first_name: me.first_name,
         }
     }
 
-    /// Create a Telegram API client instance, and initiate a connection.
-    fn create_telegram_client() -> Api {
-        // Retrieve the Telegram bot token
-        let token = env::var("TELEGRAM_BOT_TOKEN").expect("env var TELEGRAM_BOT_TOKEN not set");
+    /// Get the identity of the bot.
+    pub fn bot(&self) -> &BotIdentity {
+        &self.inner.bot
+    }
 
-        // Initiate the Telegram API client, and return
-        Api::new(token)
+    /// Get the configuration.
+    pub fn config(&self) -> &Config {
+        &self.inner.config
     }
 
     /// Get the database connection.
@@ -65,11 +135,35 @@ impl State {
     }
 
     /// Get the database connection.
+    ///
+    /// Panics if no connection is available, after raising an operator alert.
     pub fn db_connection(&self) -> DbPooled {
-        self.inner
-            .db
-            .get()
-            .expect("failed to get database connection from pool")
+        self.inner.db.get().unwrap_or_else(|err| {
+            self.alerts().record(AlertKind::Database, err.to_string());
+            panic!("failed to get database connection from pool: {}", err)
+        })
+    }
+
+    /// Run the given query with a pooled database connection, on a blocking thread.
+    ///
+    /// Failing to get a connection is reported as operator alert.
+    pub async fn db_query<T, F>(&self, query: F) -> Result<T, QueryError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&DbPooled) -> QueryResult<T> + Send + 'static,
+    {
+        let db = self.db().clone();
+        let result = task::spawn_blocking(move || {
+            let connection = db.get().map_err(QueryError::Connect)?;
+            query(&connection).map_err(QueryError::Query)
+        })
+        .await
+        .map_err(QueryError::Blocking)
+        .and_then(|result| result);
+        if let Err(QueryError::Connect(err)) = &result {
+            self.alerts().record(AlertKind::Database, err.to_string());
+        }
+        result
     }
 
     /// Get the Telegram API client.
@@ -77,159 +171,393 @@ impl State {
         &self.telegram_client
     }
 
-    /// Send a request using the Telegram API client, and track the messages the bot sends.
-    /// Because the stats of this message need to be tracked, it only allows to send requests that
-    /// have a `Message` as response.
+    /// Send a request that responds with a message to Telegram, and track the messages the bot
+    /// sends.
+    ///
+    /// Requests Telegram refuses are reported as `SendError::NotSent`, use `send_outcome` to react
+    /// to these instead. See `send_outcome` for details.
+    pub async fn send<Req, M>(&self, request: Req) -> Result<M, SendError>
+    where
+        Req: Request<Response = JsonIdResponse<M>>,
+        M: SentMessage,
+    {
+        self.send_outcome(request)
+            .await
+            .and_then(SendOutcome::into_sent)
+    }
+
+    /// Send a request that responds with a message to Telegram, and track the messages the bot
+    /// sends.
+    ///
+    /// The request is sent through the transport directly, to classify the response into a
+    /// `SendOutcome` callers may react to. Transient network and Telegram server errors are retried
+    /// with an exponential backoff. If a group was upgraded to a supergroup, its stats are moved to
+    /// the new chat and messages are sent there instead. Sent messages are tracked in the stats,
+    /// failures are logged.
     /// This function uses a fixed timeout internally.
-    pub async fn telegram_send<Req>(
-        &self,
-        request: Req,
-    ) -> Result<Option<MessageOrChannelPost>, TelegramError>
+    pub async fn send_outcome<Req, M>(&self, request: Req) -> Result<SendOutcome<M>, SendError>
     where
-        Req: Request<Response = JsonIdResponse<MessageOrChannelPost>>,
+        Req: Request<Response = JsonIdResponse<M>>,
+        M: SentMessage,
     {
-        // Clone the state for use in this future
-        let state = self.clone();
+        let outcome = match request.serialize() {
+            Ok(request) => self.send_request(&request).await,
+            Err(err) => Err(SendError::Serialize(err)),
+        };
+        match &outcome {
+            Ok(SendOutcome::Sent(msg)) => msg.track(self.stats()),
+            Ok(outcome) => warn!(%outcome, "Telegram did not send message"),
+            Err(err) => {
+                metrics::TELEGRAM_SEND_ERRORS.inc();
+                error!(%err, "Telegram send error");
+            }
+        }
+        outcome
+    }
 
-        // Send the message through the Telegram client, track the response for stats
-        let future = self
-            .telegram_client()
-            .send_timeout(request, Duration::from_secs(10))
-            .inspect(move |msg| {
-                // Unpack message, report errors
-                let msg = match msg {
-                    Ok(msg) => msg,
-                    Err(err) => {
-                        eprintln!("Telegram send error: {}", err);
-                        return;
-                    }
-                };
-
-                if let Some(msg) = msg {
-                    let edit_date = match msg {
-                        MessageOrChannelPost::Message(msg) => msg.edit_date,
-                        MessageOrChannelPost::ChannelPost(post) => post.edit_date,
-                    };
-
-                    if edit_date.is_none() {
-                        state
-                            .stats()
-                            .increase_message_or_channel_post_stats(msg, 1, 0);
-                    } else {
-                        state
-                            .stats()
-                            .increase_message_or_channel_post_stats(msg, 0, 1);
-                    }
-                }
-            });
+    /// Send a serialized request to Telegram, and follow a chat migration by sending it to the new
+    /// chat once.
+    async fn send_request<M>(&self, request: &HttpRequest) -> Result<SendOutcome<M>, SendError>
+    where
+        M: SentMessage,
+    {
+        let outcome = self.try_send(request).await?;
+        let to = match outcome {
+            SendOutcome::ChatMigrated { to } => to,
+            outcome => return Ok(outcome),
+        };
+        match send::migrate_request(request, to) {
+            Some((from, migrated)) => {
+                info!(%from, %to, "chat migrated to supergroup, moving stats and resending");
+                self.migrate_chat(from, to).await;
+                self.try_send(&migrated).await
+            }
+            None => Ok(SendOutcome::ChatMigrated { to }),
+        }
+    }
 
-        future.await
+    /// Move the stats of a chat to the supergroup it was upgraded to.
+    ///
+    /// Failures are logged, as the message is sent to the new chat either way.
+    async fn migrate_chat(&self, from: ChatId, to: ChatId) {
+        let state = self.clone();
+        let result = self
+            .db_query(move |connection| state.stats().migrate_chat(from, to, connection))
+            .await;
+        if let Err(err) = result {
+            error!(%err, %from, %to, "failed to move stats of migrated chat");
+        }
     }
 
-    // TODO: merge with telegram_send()
-    /// Send a request using the Telegram API client, and track the messages the bot sends.
-    /// Because the stats of this message need to be tracked, it only allows to send requests that
-    /// have a `Message` as response.
-    /// This function uses a fixed timeout internally.
-    pub async fn telegram_send_message<Req>(
-        &self,
-        request: Req,
-    ) -> Result<Option<Message>, TelegramError>
+    /// Send a request that responds with a message to Telegram, see `send`.
+    ///
+    /// This function spawns the request on the background and runs it to completion. The outcome
+    /// is logged, use `send` to react to it.
+    pub fn spawn_send<Req, M>(&self, request: Req)
     where
-        Req: Request<Response = JsonIdResponse<Message>>,
+        Req: Request<Response = JsonIdResponse<M>> + Send + 'static,
+        M: SentMessage,
     {
-        // Clone the state for use in this future
-        let state = self.clone();
+        let cloned = self.clone();
+        self.spawn(async move {
+            let _ = cloned.send_outcome(request).await;
+        });
+    }
 
-        // Send the message through the Telegram client, track the response for stats
-        let future = self
-            .telegram_client()
-            .send_timeout(request, Duration::from_secs(10))
-            .inspect(move |msg| {
-                // Unpack message, report errors
-                let msg = match msg {
-                    Ok(msg) => msg,
-                    Err(err) => {
-                        eprintln!("Telegram send error: {}", err);
-                        return;
-                    }
-                };
-
-                if let Some(msg) = msg {
-                    if msg.edit_date.is_none() {
-                        state.stats().increase_message_stats(msg, 1, 0);
-                    } else {
-                        state.stats().increase_message_stats(msg, 0, 1);
-                    }
+    /// Send a serialized request to Telegram, retrying on transient errors, and classify the
+    /// response.
+    async fn try_send<M>(&self, request: &HttpRequest) -> Result<SendOutcome<M>, SendError>
+    where
+        M: SentMessage,
+    {
+        // Send the request, retry with backoff on network and server errors
+        let mut retries = 0;
+        let response = loop {
+            let result = time::timeout(
+                SEND_TIMEOUT,
+                self.transport.request(&self.token, request.clone()),
+            )
+            .await
+            .map_err(|_| SendError::Timeout)?;
+
+            let err = match result {
+                Ok(response) if retries >= SEND_RETRIES || !send::is_server_error(&response) => {
+                    break response
                 }
-            });
-
-        future.await
+                Ok(_) => "Telegram server error".to_owned(),
+                Err(err) if retries >= SEND_RETRIES => {
+                    return Err(SendError::Network(SyncFailure::new(err)))
+                }
+                Err(err) => err.to_string(),
+            };
+
+            let delay = RETRY_DELAY * 2u32.pow(retries);
+            retries += 1;
+            let delay_ms = delay.as_millis() as u64;
+            warn!(%err, retries, delay_ms, "failed to send Telegram request, retrying");
+            time::sleep(delay).await;
+        };
+
+        // Classify refused requests, or take the sent message
+        if let Some(outcome) = SendOutcome::from_response(&response) {
+            return Ok(outcome);
+        }
+        <JsonIdResponse<M> as ResponseType>::deserialize(response)
+            .map(SendOutcome::Sent)
+            .map_err(SendError::Api)
     }
 
-    /// Send a request using the Telegram API client, and track the messages the bot sends.
-    /// This function spawns the request on the background and runs it to completion.
-    /// Because the stats of this message need to be tracked, it only allows to send requests that
-    /// have a `Message` as response.
-    /// This function uses a fixed timeout internally.
-    pub fn telegram_spawn<Req>(&self, request: Req)
+    /// Spawn the given future on the runtime in the current logging span, and track it so shutdown
+    /// waits for it to complete.
+    pub fn spawn<F>(&self, future: F)
     where
-        Req: Request<Response = JsonIdResponse<Message>> + Send + 'static,
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
     {
-        let cloned = self.clone();
         self.inner
             .handle
-            .spawn(async move { cloned.telegram_send_message(request).await });
+            .spawn(self.shutdown().track(future.in_current_span()));
+    }
+
+    /// Get the health tracker.
+    pub fn health(&self) -> &Health {
+        &self.inner.health
+    }
+
+    /// Get the operator alert collector.
+    pub fn alerts(&self) -> &Alerts {
+        &self.inner.alerts
+    }
+
+    /// Get the error reporter.
+    pub fn errors(&self) -> &ErrorReporter {
+        &self.inner.errors
+    }
+
+    /// Get the command cooldown tracker.
+    pub fn cooldowns(&self) -> &Cooldowns {
+        &self.inner.cooldowns
+    }
+
+    /// Get the action permission checker.
+    pub fn permissions(&self) -> &Permissions {
+        &self.inner.permissions
+    }
+
+    /// Get the chat feature settings.
+    pub fn settings(&self) -> &Settings {
+        &self.inner.settings
+    }
+
+    /// Get the shutdown coordinator.
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.inner.shutdown
     }
 
     /// Get the stats manager.
     pub fn stats(&self) -> &Stats {
         &self.inner.stats
     }
+
+    /// Get the tracker of updates being handled.
+    pub fn updates(&self) -> &InFlight {
+        &self.inner.updates
+    }
+}
+
+/// The identity of the bot, as reported by the Telegram API.
+#[derive(Clone, Debug)]
+pub struct BotIdentity {
+    /// The user ID of the bot.
+    pub id: UserId,
+
+    /// The username of the bot, without `@` prefix.
+    pub username: String,
+
+    /// The display name of the bot.
+    pub first_name: String,
 }
 
 /// The inner state.
 struct StateInner {
+    /// The configuration.
+    config: Config,
+
+    /// The identity of the bot.
+    bot: BotIdentity,
+
     /// The database connection.
-    db: Pool<ConnectionManager<MysqlConnection>>,
+    db: DbPool,
 
     /// A handle to the reactor.
     handle: Handle,
 
     /// The stats manager.
     stats: Stats,
+
+    /// The shutdown coordinator.
+    shutdown: Shutdown,
+
+    /// The health tracker.
+    health: Health,
+
+    /// The error reporter.
+    errors: ErrorReporter,
+
+    /// The operator alert collector.
+    alerts: Alerts,
+
+    /// The command cooldown tracker.
+    cooldowns: Cooldowns,
+
+    /// The action permission checker.
+    permissions: Permissions,
+
+    /// The chat feature settings.
+    settings: Settings,
+
+    /// The tracker of updates being handled.
+    updates: InFlight,
 }
 
 impl StateInner {
     /// Initialize.
     ///
     /// This initializes the inner state.
-    /// Internally this connects to the bot database.
-    pub fn init(handle: Handle) -> StateInner {
+    /// Internally this prepares the given bot database.
+    pub fn init(config: Config, handle: Handle, bot: BotIdentity, db: DbPool) -> StateInner {
+        Self::migrate(&db, &config);
+        let alerts = Alerts::new(config.alerts.chat.is_some());
+
         StateInner {
-            db: Self::connection_pool(),
+            config,
+            bot,
+            db,
             handle,
             stats: Stats::new(),
+            shutdown: Shutdown::new(),
+            health: Health::new(),
+            errors: ErrorReporter::new(),
+            alerts,
+            cooldowns: Cooldowns::new(),
+            permissions: Permissions::new(),
+            settings: Settings::new(),
+            updates: InFlight::new(),
+        }
+    }
+
+    /// Create database connection pool.
+    ///
+    /// The database backend is selected by the scheme of the database URL.
+    fn connection_pool() -> DbPool {
+        DbPool::from_env().unwrap_or_else(|err| panic!("Failed to connect to database: {}", err))
+    }
+
+    /// Check the database schema version, and apply pending migrations if configured.
+    ///
+    /// This refuses to start if the schema is not up-to-date afterwards.
+    fn migrate(db: &DbPool, config: &Config) {
+        match migrations::prepare(db, config.database.migrate) {
+            Ok(version) => {
+                info!(%version, backend = %db.backend(), "database schema is up-to-date")
+            }
+            Err(err) => {
+                print_error::<MigrationError>(err);
+                process::exit(1);
+            }
         }
     }
+}
 
-    /// Create database connection manager.
-    fn connection_manager() -> DbConnectionManager {
-        // Retrieve the database connection URL
-        let database_url = env::var("DATABASE_URL").expect("env var DATABASE_URL not set");
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+    use serde_json::json;
+    use telegram_bot::{prelude::*, types::ChatId};
+
+    use crate::schema::chat_user_stats;
+    use crate::send::{Error as SendError, SendOutcome};
+    use crate::testing::{Harness, GROUP_ID};
+
+    /// The chat ID of the supergroup the test group is upgraded to.
+    const SUPERGROUP_ID: i64 = -1_000_000_000_200;
+
+    /// Count the messages in the given chat stored in the database.
+    fn stored_messages(harness: &Harness, chat: i64) -> i32 {
+        let connection = harness.state.db_connection();
+        with_connection!(connection, |conn| chat_user_stats::dsl::chat_user_stats
+            .filter(chat_user_stats::dsl::chat_id.eq(chat))
+            .select(chat_user_stats::dsl::messages)
+            .load::<i32>(conn))
+        .unwrap()
+        .into_iter()
+        .sum()
+    }
 
-        // Test connection to database
-        MysqlConnection::establish(&database_url)
-            .unwrap_or_else(|_| panic!("Failed to connect to database on {}", database_url));
+    #[tokio::test]
+    async fn send_fails_if_refused() {
+        let harness = Harness::new().await;
+        harness
+            .transport
+            .script_error("sendMessage", 403, "Forbidden: bot was kicked");
+
+        let result = harness
+            .state
+            .send(ChatId::new(GROUP_ID).text("Hello"))
+            .await;
+        assert!(matches!(result, Err(SendError::NotSent(_))));
+    }
 
-        // Build and return connection manager
-        DbConnectionManager::new(database_url)
+    #[tokio::test]
+    async fn send_outcome_reports_refusal() {
+        let harness = Harness::new().await;
+        harness
+            .transport
+            .script_error("sendMessage", 429, "Too Many Requests");
+
+        let outcome = harness
+            .state
+            .send_outcome(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, SendOutcome::RateLimited { .. }));
     }
 
-    /// Create database connection pool.
-    fn connection_pool() -> DbPool {
-        Pool::builder()
-            .build(Self::connection_manager())
-            .expect("Failed to create pool.")
+    #[tokio::test]
+    async fn send_follows_chat_migration() {
+        let harness = Harness::new().await;
+        harness
+            .state
+            .send(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        assert!(harness.state.stats().flush(harness.state.db()));
+        assert_eq!(stored_messages(&harness, GROUP_ID), 1);
+
+        harness.transport.script(
+            "sendMessage",
+            json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: group chat was upgraded to a supergroup chat",
+                "parameters": { "migrate_to_chat_id": SUPERGROUP_ID },
+            }),
+        );
+        let sent = harness
+            .state
+            .send(ChatId::new(GROUP_ID).text("Hello again"))
+            .await
+            .unwrap();
+        assert_eq!(sent.chat.id(), ChatId::new(SUPERGROUP_ID));
+
+        let requests = harness.transport.sent();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].body["chat_id"], GROUP_ID);
+        assert_eq!(requests[2].body["chat_id"], SUPERGROUP_ID);
+
+        // Stats of the group are moved to the supergroup
+        assert!(harness.state.stats().flush(harness.state.db()));
+        assert_eq!(stored_messages(&harness, GROUP_ID), 0);
+        assert_eq!(stored_messages(&harness, SUPERGROUP_ID), 2);
     }
 }
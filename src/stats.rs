@@ -4,15 +4,15 @@ use std::sync::Mutex;
 use chrono::NaiveDateTime;
 use diesel::{
     self,
-    mysql::MysqlConnection,
     prelude::*,
     result::{Error as DieselError, QueryResult},
 };
-use telegram_bot::types::{ChatId, Message, MessageKind, MessageOrChannelPost, UserId};
+use telegram_bot::types::{ChatId, Message, MessageId, MessageKind, MessageOrChannelPost, UserId};
 
+use crate::db::{DbPool, DbPooled};
+use crate::metrics;
 use crate::models::{Chat, ChatUserStats, User};
 use crate::schema::{chat, chat_user_stats, user};
-use crate::state::{DbPool, DbPooled};
 
 pub struct Stats {
     /// A queue of stats that still needs to be pushed to the database.
@@ -66,8 +66,9 @@ impl Stats {
                         .or_insert((0, 0));
                     entry.0 += messages;
                     entry.1 += edits;
+                    metrics::STATS_QUEUE_DEPTH.set(queue_depth(queue));
                 }
-                Err(_) => eprintln!("ERR: failed lock stats queue, unable to increase user stats"),
+                Err(_) => error!("failed lock stats queue, unable to increase user stats"),
             }
         }
 
@@ -80,7 +81,7 @@ impl Stats {
                     message.from.last_name.to_owned(),
                 ));
             }
-            Err(_) => eprintln!("ERR: failed lock stats queue, unable to increase user stats"),
+            Err(_) => error!("failed lock stats queue, unable to increase user stats"),
         }
     }
 
@@ -94,23 +95,37 @@ impl Stats {
     /// Flush the queue with stats to the database.
     /// Items successfully pushed to the database are cleared from the queue.
     /// Any errors while flushing are reported in the console.
-    pub fn flush(&self, db: &DbPool) {
-        match (self.queue.lock(), self.queue_names.lock()) {
-            (Ok(ref mut chats), Ok(ref mut names)) => {
-                let connection = db
-                    .get()
-                    .expect("failed to get database connection from pool");
-                Self::flush_chats(chats, names, &connection);
+    ///
+    /// Returns `true` if all queued stats were flushed.
+    pub fn flush(&self, db: &DbPool) -> bool {
+        let timer = metrics::STATS_FLUSH_DURATION.start_timer();
+        let flushed = match (self.queue.lock(), self.queue_names.lock()) {
+            (Ok(ref mut chats), Ok(ref mut names)) => match db.get() {
+                Ok(connection) => {
+                    Self::flush_chats(chats, names, &connection);
+                    metrics::STATS_QUEUE_DEPTH.set(queue_depth(chats));
+                    chats.is_empty()
+                }
+                Err(err) => {
+                    error!(%err, "failed to get database connection, unable to flush stats");
+                    false
+                }
+            },
+            (Err(err), _) => {
+                error!(%err, "failed lock stats queue, unable to flush to database");
+                false
             }
-            (Err(err), _) => eprintln!(
-                "ERR: failed lock stats queue, unable to flush to database: {}",
-                err
-            ),
-            (_, Err(err)) => eprintln!(
-                "ERR: failed lock stats names queue, unable to flush to database: {}",
-                err
-            ),
+            (_, Err(err)) => {
+                error!(%err, "failed lock stats names queue, unable to flush to database");
+                false
+            }
+        };
+        timer.observe_duration();
+
+        if !flushed {
+            metrics::STATS_FLUSH_FAILURES.inc();
         }
+        flushed
     }
 
     /// Flush all chats from the queue to the database
@@ -119,14 +134,14 @@ impl Stats {
     pub fn flush_chats(
         chats: &mut HashMap<ChatId, HashMap<UserId, HashMap<StatsKind, (u32, u32)>>>,
         names: &mut HashMap<UserId, (Option<String>, String, Option<String>)>,
-        connection: &MysqlConnection,
+        connection: &DbPooled,
     ) {
         // Flush each chat, remove the successfully flushed
         chats.retain(|chat, ref mut users| {
             // Find an existing entry in the database and update it, or create a new entry
-            match chat::dsl::chat
+            match with_connection!(connection, |conn| chat::dsl::chat
                 .find(chat.to_i64())
-                .first::<Chat>(connection)
+                .first::<Chat>(conn))
             {
                 Ok(mut _existing) => {
                     // TODO: update if the title changed
@@ -135,23 +150,18 @@ impl Stats {
                     //     .execute(connection);
                 }
                 Err(DieselError::NotFound) => {
-                    if let Err(err) = diesel::insert_into(chat::dsl::chat)
-                        .values(chat::dsl::telegram_id.eq(chat.to_i64()))
-                        .execute(connection)
+                    if let Err(err) =
+                        with_connection!(connection, |conn| diesel::insert_into(chat::dsl::chat)
+                            .values(chat::dsl::telegram_id.eq(chat.to_i64()))
+                            .execute(conn))
                     {
-                        eprintln!(
-                            "ERR: failed to create queued chat in database, skipping: {}",
-                            err
-                        );
-                        return false;
+                        error!(%err, "failed to create queued chat in database, skipping");
+                        return true;
                     }
                 }
                 Err(err) => {
-                    eprintln!(
-                        "ERR: failed to check if queued chat exists in the database, skipping: {}",
-                        err
-                    );
-                    return false;
+                    error!(%err, "failed to check if queued chat exists in the database, skipping");
+                    return true;
                 }
             }
 
@@ -171,25 +181,25 @@ impl Stats {
         chat: ChatId,
         users: &mut HashMap<UserId, HashMap<StatsKind, (u32, u32)>>,
         names: &mut HashMap<UserId, (Option<String>, String, Option<String>)>,
-        connection: &MysqlConnection,
+        connection: &DbPooled,
     ) {
         // Flush all users in this chat, remove successfully flushed
         users.retain(|user, stats| {
             // Find an existing entry in the database and update it, or create a new entry
-            match user::dsl::user.find(user.to_i64()).first::<User>(connection) {
+            match with_connection!(connection, |conn| user::dsl::user.find(user.to_i64()).first::<User>(conn)) {
                 Ok(existing) => {
                     // Update the existing user if details changed
                     if let Some((user_username, first, last)) = names.get(user) {
                         if *user_username != existing.username || *first != existing.first_name || *last != existing.last_name {
-                            if let Err(err) = diesel::update(&existing)
+                            if let Err(err) = with_connection!(connection, |conn| diesel::update(&existing)
                                 .set((
                                     user::dsl::username.eq(user_username),
                                     user::dsl::first_name.eq(first),
                                     user::dsl::last_name.eq(last),
                                 ))
-                                .execute(connection)
+                                .execute(conn))
                             {
-                                eprintln!("ERR: failed to update name of queued user in database, skipping: {}", err);
+                                error!(%err, "failed to update name of queued user in database, skipping");
                                 return true;
                             }
                         }
@@ -201,22 +211,22 @@ impl Stats {
                 Err(DieselError::NotFound) => {
                     // Insert the user into the database, with it's name if known
                     let result = if let Some(name) = names.get(user) {
-                        diesel::insert_into(user::dsl::user)
+                        with_connection!(connection, |conn| diesel::insert_into(user::dsl::user)
                             .values((
                                 user::dsl::telegram_id.eq(user.to_i64()),
                                 user::dsl::username.eq(name.0.clone()),
                                 user::dsl::first_name.eq(name.1.clone()),
                                 user::dsl::last_name.eq(name.2.clone()),
                             ))
-                            .execute(connection)
+                            .execute(conn))
                     } else {
-                        diesel::insert_into(user::dsl::user)
+                        with_connection!(connection, |conn| diesel::insert_into(user::dsl::user)
                             .values(user::dsl::telegram_id.eq(user.to_i64()))
-                            .execute(connection)
+                            .execute(conn))
                     };
 
                     if let Err(err) = result {
-                        eprintln!("ERR: failed to create queued user in database, skipping: {}", err);
+                        error!(%err, "failed to create queued user in database, skipping");
                         return true;
                     }
 
@@ -224,7 +234,7 @@ impl Stats {
                     names.remove(user);
                 },
                 Err(err) => {
-                    eprintln!("ERR: failed to check if queued user exists in the database, skipping: {}", err);
+                    error!(%err, "failed to check if queued user exists in the database, skipping");
                     return true;
                 },
             }
@@ -242,17 +252,14 @@ impl Stats {
         chat: ChatId,
         user: UserId,
         stats: &mut HashMap<StatsKind, (u32, u32)>,
-        connection: &MysqlConnection,
+        connection: &DbPooled,
     ) {
         // Flush all message types for the given user, remove successfully flushed
         stats.retain(|message_type, (messages, edits)| {
             let result =
                 Self::flush_user_stats(chat, user, *message_type, *messages, *edits, connection);
             if let Err(ref err) = result {
-                eprintln!(
-                    "ERR: failed to flush chat user stats to database, skipping: {}",
-                    err,
-                );
+                error!(%err, "failed to flush chat user stats to database, skipping");
             }
             result.is_err()
         });
@@ -267,35 +274,97 @@ impl Stats {
         message_type: StatsKind,
         messages: u32,
         edits: u32,
-        connection: &MysqlConnection,
+        connection: &DbPooled,
     ) -> Result<(), DieselError> {
         // Find an existing entry in the database and update it, or create a new entry
-        match chat_user_stats::dsl::chat_user_stats
-            .find((chat.to_i64(), user.to_i64(), message_type.id()))
-            .first::<ChatUserStats>(connection)
-        {
-            Ok(existing) => diesel::update(&existing)
-                .set((
-                    chat_user_stats::dsl::messages
-                        .eq(chat_user_stats::dsl::messages + messages as i32),
-                    chat_user_stats::dsl::edits.eq(chat_user_stats::dsl::edits + edits as i32),
-                ))
-                .execute(connection)
-                .map(|_| ()),
-            Err(DieselError::NotFound) => {
-                diesel::insert_into(chat_user_stats::dsl::chat_user_stats)
-                    .values((
-                        chat_user_stats::dsl::chat_id.eq(chat.to_i64()),
-                        chat_user_stats::dsl::user_id.eq(user.to_i64()),
-                        chat_user_stats::dsl::message_type.eq(message_type.id()),
-                        chat_user_stats::dsl::messages.eq(messages as i32),
-                        chat_user_stats::dsl::edits.eq(edits as i32),
+        with_connection!(connection, |conn| {
+            match chat_user_stats::dsl::chat_user_stats
+                .find((chat.to_i64(), user.to_i64(), message_type.id()))
+                .first::<ChatUserStats>(conn)
+            {
+                Ok(existing) => diesel::update(&existing)
+                    .set((
+                        chat_user_stats::dsl::messages
+                            .eq(chat_user_stats::dsl::messages + messages as i32),
+                        chat_user_stats::dsl::edits.eq(chat_user_stats::dsl::edits + edits as i32),
                     ))
-                    .execute(connection)
-                    .map(|_| ())
+                    .execute(conn)
+                    .map(|_| ()),
+                Err(DieselError::NotFound) => {
+                    diesel::insert_into(chat_user_stats::dsl::chat_user_stats)
+                        .values((
+                            chat_user_stats::dsl::chat_id.eq(chat.to_i64()),
+                            chat_user_stats::dsl::user_id.eq(user.to_i64()),
+                            chat_user_stats::dsl::message_type.eq(message_type.id()),
+                            chat_user_stats::dsl::messages.eq(messages as i32),
+                            chat_user_stats::dsl::edits.eq(edits as i32),
+                        ))
+                        .execute(conn)
+                        .map(|_| ())
+                }
+                err => err.map(|_| ()),
+            }
+        })
+    }
+
+    /// Move all stats of a chat to the supergroup it was upgraded to.
+    /// Queued stats are moved along, stats in the database are merged into those of the new chat.
+    /// If the operation failed, an error is returned.
+    pub fn migrate_chat(&self, from: ChatId, to: ChatId, connection: &DbPooled) -> QueryResult<()> {
+        // Move queued stats to the new chat
+        match self.queue.lock() {
+            Ok(ref mut queue) => {
+                if let Some(users) = queue.remove(&from) {
+                    let chat = queue.entry(to).or_insert_with(HashMap::new);
+                    for (user, stats) in users {
+                        let user = chat.entry(user).or_insert_with(HashMap::new);
+                        for (message_type, (messages, edits)) in stats {
+                            let entry = user.entry(message_type).or_insert((0, 0));
+                            entry.0 += messages;
+                            entry.1 += edits;
+                        }
+                    }
+                }
             }
-            err => err.map(|_| ()),
+            Err(_) => error!("failed lock stats queue, unable to migrate queued chat stats"),
         }
+
+        // Merge the stats in the database into the new chat
+        with_connection!(connection, |conn| conn.transaction(|| {
+            let existing = chat_user_stats::dsl::chat_user_stats
+                .filter(chat_user_stats::dsl::chat_id.eq(from.to_i64()))
+                .load::<ChatUserStats>(conn)?;
+            if existing.is_empty() {
+                return Ok(());
+            }
+
+            match chat::dsl::chat.find(to.to_i64()).first::<Chat>(conn) {
+                Ok(_) => {}
+                Err(DieselError::NotFound) => {
+                    diesel::insert_into(chat::dsl::chat)
+                        .values(chat::dsl::telegram_id.eq(to.to_i64()))
+                        .execute(conn)?;
+                }
+                Err(err) => return Err(err),
+            }
+
+            for stats in existing {
+                let message_type = match StatsKind::from_id(stats.message_type) {
+                    Some(message_type) => message_type,
+                    None => continue,
+                };
+                Self::flush_user_stats(
+                    to,
+                    UserId::new(stats.user_id),
+                    message_type,
+                    stats.messages as u32,
+                    stats.edits as u32,
+                    connection,
+                )?;
+                diesel::delete(&stats).execute(conn)?;
+            }
+            Ok(())
+        }))
     }
 
     /// Fetch chat stats.
@@ -313,7 +382,7 @@ impl Stats {
         // Get all message stats associated with this chat
         // TODO: do a left join instead
         let all_stats: Vec<(i64, Option<String>, String, Option<String>, i16, i32, i32)> =
-            chat_user_stats
+            with_connection!(connection, |conn| chat_user_stats
                 .inner_join(user::table)
                 .select((
                     user_id,
@@ -325,7 +394,7 @@ impl Stats {
                     edits,
                 ))
                 .filter(chat_id.eq(selected_chat.to_i64()))
-                .load(&connection)?;
+                .load(conn))?;
 
         // Build a hashmap of user totals, add database and queue stats
         let mut user_totals: HashMap<
@@ -444,12 +513,12 @@ impl Stats {
         let total_edits = user_totals.iter().map(|(_, _, _, _, n)| n).sum();
 
         // Get the time we started recording stats at
-        let since = chat_user_stats
+        let since = with_connection!(connection, |conn| chat_user_stats
             .select(created_at)
             .filter(chat_id.eq(selected_chat.to_i64()))
             .order(created_at.asc())
-            .first::<NaiveDateTime>(&connection)
-            .ok();
+            .first::<NaiveDateTime>(conn))
+        .ok();
 
         // Build the chat stats
         Ok(ChatStats::new(
@@ -462,6 +531,15 @@ impl Stats {
     }
 }
 
+/// Count the number of stats entries in the given queue.
+fn queue_depth(queue: &HashMap<ChatId, HashMap<UserId, HashMap<StatsKind, (u32, u32)>>>) -> i64 {
+    queue
+        .values()
+        .flat_map(|users| users.values())
+        .map(|kinds| kinds.len() as i64)
+        .sum()
+}
+
 /// An object holding stats for a chat and optionally for a user.
 pub struct ChatStats {
     /// A list of users and the number of messages and edits they made.
@@ -700,3 +778,9 @@ impl TelegramToI64 for UserId {
         self.to_string().parse().unwrap()
     }
 }
+
+impl TelegramToI64 for MessageId {
+    fn to_i64(&self) -> i64 {
+        self.to_string().parse().unwrap()
+    }
+}
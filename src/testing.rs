@@ -0,0 +1,137 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+use serde_json::{json, Value};
+use telegram_bot::types::Message;
+use tokio::runtime::Handle;
+
+use crate::config::{Config, TransportKind};
+use crate::db::DbPool;
+use crate::msg::handler::{Error as HandlerError, Handler};
+use crate::state::State;
+use crate::transport::fake::FakeTransport;
+
+/// The user ID of the user sending test messages.
+pub const USER_ID: i64 = 100;
+
+/// The chat ID of the group test messages are sent in.
+pub const GROUP_ID: i64 = -200;
+
+/// The chat ID of the private chat with the user sending test messages.
+pub const PRIVATE_ID: i64 = USER_ID;
+
+/// The number of test databases created by this process, to give each harness its own.
+static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/// The ID of the last test message that was built.
+static MESSAGE_ID: AtomicI64 = AtomicI64::new(0);
+
+/// A harness to drive the bot offline, against a fake Telegram API and a temporary database.
+pub struct Harness {
+    /// The global state of the bot under test.
+    pub state: State,
+
+    /// The fake Telegram API the bot sends through, to script and inspect requests.
+    pub transport: FakeTransport,
+
+    /// The path of the temporary SQLite database, removed when dropped.
+    database: PathBuf,
+}
+
+impl Harness {
+    /// Set up a bot with the default configuration.
+    pub async fn new() -> Self {
+        Self::with_config(Config::default()).await
+    }
+
+    /// Set up a bot with the given configuration, using the fake transport.
+    pub async fn with_config(mut config: Config) -> Self {
+        config.telegram.transport = TransportKind::Fake;
+
+        let database = env::temp_dir().join(format!(
+            "risc-bot-test-{}-{}.sqlite",
+            process::id(),
+            DATABASES.fetch_add(1, Ordering::SeqCst),
+        ));
+        let db = DbPool::connect(&format!("sqlite://{}", database.display()))
+            .expect("failed to create test database");
+
+        let transport = FakeTransport::new();
+        let state =
+            State::init_with_transport(config, Handle::current(), Box::new(transport.clone()), db)
+                .await;
+
+        // Forget the identity request made while initializing
+        transport.clear();
+
+        Harness {
+            state,
+            transport,
+            database,
+        }
+    }
+
+    /// Handle the given message like it was received from Telegram.
+    pub async fn handle(&self, msg: Message) -> Result<(), HandlerError> {
+        Handler::handle(self.state.clone(), msg).await
+    }
+
+    /// Get the text of all messages that were sent, in order.
+    pub fn sent_texts(&self) -> Vec<String> {
+        self.transport
+            .sent()
+            .into_iter()
+            .map(|request| request.body["text"].as_str().unwrap_or_default().to_owned())
+            .collect()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.database);
+    }
+}
+
+/// Build a text message from the test user in the given chat.
+///
+/// Chats with a negative ID are groups, other chats are private.
+pub fn message(chat: i64, text: &str) -> Message {
+    from_json(message_json(chat, text))
+}
+
+/// Build a text message from the test user in the given chat, replying to another message.
+pub fn reply(chat: i64, text: &str, reply_to: &str) -> Message {
+    let mut json = message_json(chat, text);
+    json["reply_to_message"] = message_json(chat, reply_to);
+    from_json(json)
+}
+
+/// Build the JSON object of an update with the given ID, for a text message from the test user
+/// in the given chat.
+pub fn update_json(id: i64, chat: i64, text: &str) -> Value {
+    json!({ "update_id": id, "message": message_json(chat, text) })
+}
+
+/// Build the JSON object of a text message from the test user in the given chat.
+fn message_json(chat: i64, text: &str) -> Value {
+    let chat = if chat < 0 {
+        json!({ "id": chat, "type": "group", "title": "Test group" })
+    } else {
+        json!({ "id": chat, "type": "private", "first_name": "Tester" })
+    };
+    json!({
+        "message_id": MESSAGE_ID.fetch_add(1, Ordering::SeqCst) + 1,
+        "from": { "id": USER_ID, "is_bot": false, "first_name": "Tester", "username": "tester" },
+        "chat": chat,
+        "date": 0,
+        "text": text,
+    })
+}
+
+/// Deserialize a message from the given JSON object, like it was received from Telegram.
+fn from_json(json: Value) -> Message {
+    serde_json::from_value(json).expect("failed to build test message")
+}
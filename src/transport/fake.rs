@@ -0,0 +1,318 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::prelude::*;
+use serde_json::{json, Value};
+use telegram_bot::{
+    types::{Body, HttpRequest, HttpResponse},
+    Error as TelegramError,
+};
+
+use super::{method_name, Transport};
+
+/// The user ID of the fake bot.
+const BOT_ID: i64 = 1;
+
+/// The username of the fake bot.
+const BOT_USERNAME: &str = "riscbot";
+
+/// An in-memory Telegram transport, that does not send anything.
+///
+/// All requests are recorded, to inspect what the bot would have sent. Responses are generated
+/// for common requests, such as a message for each sent or edited message, and may be scripted
+/// per API method to simulate specific responses and errors.
+///
+/// Clones share the same recording and script.
+#[derive(Clone, Debug, Default)]
+pub struct FakeTransport {
+    /// The shared recording and script.
+    inner: Arc<Mutex<FakeInner>>,
+
+    /// Whether to print each recorded request.
+    verbose: bool,
+}
+
+/// The recording and script of a fake transport.
+#[derive(Debug, Default)]
+struct FakeInner {
+    /// All requests that were made, in order.
+    requests: Vec<RecordedRequest>,
+
+    /// Scripted responses to return for the next requests, per API method.
+    script: HashMap<String, VecDeque<Value>>,
+
+    /// The ID of the last message that was sent.
+    last_message_id: i64,
+}
+
+/// A request recorded by the fake transport.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    /// The API method, such as `sendMessage`.
+    pub method: String,
+
+    /// The JSON body of the request, `null` if it had none.
+    pub body: Value,
+}
+
+impl FakeTransport {
+    /// Construct a new fake transport, without any recorded requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Print each recorded request to stdout.
+    pub fn verbose(mut self) -> Self {
+        self.verbose = true;
+        self
+    }
+
+    /// Get all recorded requests, in order.
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+
+    /// Get all recorded requests for the given API method, in order.
+    #[cfg(test)]
+    pub fn requests_for(&self, method: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method == method)
+            .collect()
+    }
+
+    /// Get all messages that were sent.
+    #[cfg(test)]
+    pub fn sent(&self) -> Vec<RecordedRequest> {
+        self.requests_for("sendMessage")
+    }
+
+    /// Get all messages that were edited.
+    #[cfg(test)]
+    pub fn edited(&self) -> Vec<RecordedRequest> {
+        self.requests_for("editMessageText")
+    }
+
+    /// Clear all recorded requests.
+    #[cfg(test)]
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().requests.clear();
+    }
+
+    /// Script a raw response for the next request with the given API method.
+    ///
+    /// The response must be a full Telegram API response object, such as
+    /// `{"ok": true, "result": ...}`. Scripted responses are returned in order.
+    #[cfg(test)]
+    pub fn script(&self, method: &str, response: Value) {
+        self.inner
+            .lock()
+            .unwrap()
+            .script
+            .entry(method.into())
+            .or_insert_with(VecDeque::new)
+            .push_back(response);
+    }
+
+    /// Script a successful result for the next request with the given API method.
+    #[cfg(test)]
+    pub fn script_result(&self, method: &str, result: Value) {
+        self.script(method, json!({ "ok": true, "result": result }));
+    }
+
+    /// Script an error for the next request with the given API method.
+    #[cfg(test)]
+    pub fn script_error(&self, method: &str, code: u16, description: &str) {
+        self.script(
+            method,
+            json!({ "ok": false, "error_code": code, "description": description }),
+        );
+    }
+
+    /// Record the given request, and build the response to return for it.
+    fn respond(&self, request: HttpRequest) -> (Value, Option<Duration>) {
+        let method = method_name(&request);
+        let body = match request.body {
+            Body::Json(ref json) => serde_json::from_str(json).unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+
+        let recorded = RecordedRequest {
+            method: method.clone(),
+            body: body.clone(),
+        };
+        if self.verbose {
+            info!(method = %recorded.method, body = %recorded.body, "fake Telegram API request");
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.requests.push(recorded);
+
+        // Return a scripted response if there is any
+        if let Some(response) = inner.script.get_mut(&method).and_then(|s| s.pop_front()) {
+            return (response, None);
+        }
+
+        // Generate a response
+        let result = match method.as_str() {
+            "getMe" => bot_user(),
+            "getUpdates" => {
+                // Wait for the long polling timeout, as there are no updates
+                let timeout = body["timeout"].as_u64().unwrap_or(0);
+                return (
+                    json!({ "ok": true, "result": [] }),
+                    Some(Duration::from_secs(timeout)),
+                );
+            }
+            "sendMessage" => {
+                inner.last_message_id += 1;
+                fake_message(inner.last_message_id, &body, false)
+            }
+            "editMessageText" => {
+                let id = body["message_id"].as_i64().unwrap_or_default();
+                fake_message(id, &body, true)
+            }
+            _ => Value::Bool(true),
+        };
+        (json!({ "ok": true, "result": result }), None)
+    }
+}
+
+impl Transport for FakeTransport {
+    fn request(
+        &self,
+        _token: &str,
+        request: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, TelegramError>> + Send>> {
+        let (response, delay) = self.respond(request);
+        let response = HttpResponse {
+            body: Some(response.to_string().into_bytes()),
+        };
+        Box::pin(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Build the user object of the fake bot.
+fn bot_user() -> Value {
+    json!({
+        "id": BOT_ID,
+        "is_bot": true,
+        "first_name": "RISC",
+        "username": BOT_USERNAME,
+    })
+}
+
+/// Build a message object the bot sent, from the given send or edit request body.
+fn fake_message(id: i64, body: &Value, edited: bool) -> Value {
+    let chat_id = body["chat_id"].as_i64().unwrap_or_default();
+    let chat = if chat_id < 0 {
+        json!({ "id": chat_id, "type": "group", "title": "Fake group" })
+    } else {
+        json!({ "id": chat_id, "type": "private", "first_name": "Fake user" })
+    };
+    let now = Utc::now().timestamp();
+
+    let mut message = json!({
+        "message_id": id,
+        "from": bot_user(),
+        "chat": chat,
+        "date": now,
+        "text": body["text"].as_str().unwrap_or_default(),
+    });
+    if edited {
+        message["edit_date"] = json!(now);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use telegram_bot::{
+        prelude::*,
+        types::{ChatId, ToMessageId},
+    };
+
+    use crate::send::SendOutcome;
+    use crate::stats::TelegramToI64;
+    use crate::testing::{Harness, GROUP_ID};
+
+    #[tokio::test]
+    async fn records_sent_and_edited_messages() {
+        let harness = Harness::new().await;
+
+        let sent = harness
+            .state
+            .send(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        harness.state.send(sent.edit_text("Bye")).await.unwrap();
+
+        let sent = harness.transport.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].body["chat_id"], GROUP_ID);
+        assert_eq!(sent[0].body["text"], "Hello");
+
+        let edited = harness.transport.edited();
+        assert_eq!(edited.len(), 1);
+        assert_eq!(edited[0].body["message_id"], 1);
+        assert_eq!(edited[0].body["text"], "Bye");
+        assert_eq!(harness.transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn returns_scripted_responses_in_order() {
+        let harness = Harness::new().await;
+        harness.transport.script_error(
+            "sendMessage",
+            403,
+            "Forbidden: bot was blocked by the user",
+        );
+
+        // The scripted response is returned first, then responses are generated again
+        let outcome = harness
+            .state
+            .send_outcome(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, SendOutcome::Forbidden { .. }));
+        let outcome = harness
+            .state
+            .send_outcome(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, SendOutcome::Sent(_)));
+        assert_eq!(harness.transport.sent().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn returns_scripted_results() {
+        let harness = Harness::new().await;
+        harness.transport.script_result(
+            "sendMessage",
+            json!({
+                "message_id": 42,
+                "from": { "id": 1, "is_bot": true, "first_name": "RISC", "username": "riscbot" },
+                "chat": { "id": GROUP_ID, "type": "group", "title": "Scripted group" },
+                "date": 0,
+                "text": "Scripted",
+            }),
+        );
+
+        let sent = harness
+            .state
+            .send(ChatId::new(GROUP_ID).text("Hello"))
+            .await
+            .unwrap();
+        assert_eq!(sent.to_message_id().to_i64(), 42);
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use serde_json::Value;
+use telegram_bot::{
+    types::{Body, HttpRequest, HttpResponse},
+    Error as TelegramError,
+};
+use tokio::time;
+
+use super::{method_name, Transport};
+use crate::config::RateLimitConfig;
+use crate::metrics;
+
+/// The number of seconds a per-chat bucket must be idle, before it is cleaned up.
+const CHAT_IDLE_SECS: u64 = 300;
+
+/// The time between cleaning up idle per-chat buckets.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A transport that rate limits sending messages, to stay within Telegram limits.
+///
+/// Requests sending or editing messages wait for a token from a global bucket and from the bucket
+/// of the target chat. If Telegram still responds with a flood error, the chat is blocked for
+/// the `retry_after` time Telegram reports, and the request is sent again afterwards.
+///
+/// Other requests, such as polling for updates, are passed through as-is.
+pub struct RateLimiter {
+    inner: Arc<Inner>,
+}
+
+/// The shared rate limiter state.
+struct Inner {
+    /// The transport to send requests through.
+    transport: Box<dyn Transport>,
+
+    /// The rate limit configuration.
+    config: RateLimitConfig,
+
+    /// The global bucket, shared by all chats.
+    global: Mutex<TokenBucket>,
+
+    /// The bucket of each chat.
+    chats: Mutex<ChatBuckets>,
+}
+
+/// The token buckets of all chats that were sent to recently.
+struct ChatBuckets {
+    /// The bucket for each chat, by chat ID.
+    buckets: HashMap<String, TokenBucket>,
+
+    /// The time idle buckets were last cleaned up at.
+    pruned_at: Instant,
+}
+
+impl ChatBuckets {
+    /// Get the bucket of the given chat, create it with the given function if there is none.
+    ///
+    /// Idle buckets of other chats are cleaned up once in a while.
+    fn bucket(
+        &mut self,
+        chat: &str,
+        now: Instant,
+        create: impl FnOnce() -> TokenBucket,
+    ) -> &mut TokenBucket {
+        if now.saturating_duration_since(self.pruned_at) >= PRUNE_INTERVAL {
+            self.buckets.retain(|_, bucket| !bucket.idle(now));
+            self.pruned_at = now;
+        }
+        self.buckets.entry(chat.to_owned()).or_insert_with(create)
+    }
+}
+
+impl RateLimiter {
+    /// Wrap the given transport with a rate limiter.
+    pub fn new(transport: Box<dyn Transport>, config: RateLimitConfig) -> Self {
+        let global = TokenBucket::new(config.global_per_second, config.global_per_second);
+        RateLimiter {
+            inner: Arc::new(Inner {
+                transport,
+                config,
+                global: Mutex::new(global),
+                chats: Mutex::new(ChatBuckets {
+                    buckets: HashMap::new(),
+                    pruned_at: Instant::now(),
+                }),
+            }),
+        }
+    }
+}
+
+impl Inner {
+    /// Send the given request, waiting for rate limits and retrying on flood errors.
+    async fn send(&self, token: &str, request: HttpRequest) -> Result<HttpResponse, TelegramError> {
+        // Pass through requests that don't send messages
+        let method = method_name(&request);
+        if !is_limited(&method) {
+            return self.transport.request(token, request).await;
+        }
+        let chat = chat_id(&request);
+
+        let mut retries = 0;
+        loop {
+            // Wait for our turn
+            let wait = self.reserve(chat.as_deref());
+            if wait > Duration::from_millis(0) {
+                debug!(%method, ?chat, wait_ms = wait.as_millis() as u64, "rate limiting request");
+                time::sleep(wait).await;
+            }
+
+            // Send the request, retry after the reported time on flood errors
+            let response = self.transport.request(token, request.clone()).await?;
+            match retry_after(&response) {
+                Some(retry_after) if retries < self.config.max_retries => {
+                    metrics::TELEGRAM_RATE_LIMITED.inc();
+                    warn!(%method, ?chat, retry_after, "hit Telegram rate limit, retrying later");
+                    self.block(chat.as_deref(), Duration::from_secs(retry_after));
+                    retries += 1;
+                }
+                Some(retry_after) => {
+                    metrics::TELEGRAM_RATE_LIMITED.inc();
+                    warn!(%method, ?chat, retry_after, "hit Telegram rate limit, giving up");
+                    return Ok(response);
+                }
+                None => return Ok(response),
+            }
+        }
+    }
+
+    /// Reserve a token from the global bucket and from the bucket of the given chat.
+    ///
+    /// Returns the time to wait before the request may be sent.
+    fn reserve(&self, chat: Option<&str>) -> Duration {
+        let now = Instant::now();
+        let global = self.global.lock().unwrap().reserve(now);
+        let chat = match chat {
+            Some(chat) => self
+                .chats
+                .lock()
+                .unwrap()
+                .bucket(chat, now, || self.chat_bucket(chat))
+                .reserve(now),
+            None => Duration::from_millis(0),
+        };
+        global.max(chat)
+    }
+
+    /// Block sending to the given chat for the given time, or globally if there is no chat.
+    fn block(&self, chat: Option<&str>, duration: Duration) {
+        let now = Instant::now();
+        let until = now + duration;
+        match chat {
+            Some(chat) => self
+                .chats
+                .lock()
+                .unwrap()
+                .bucket(chat, now, || self.chat_bucket(chat))
+                .block(until),
+            None => self.global.lock().unwrap().block(until),
+        }
+    }
+
+    /// Build a new bucket for the given chat.
+    ///
+    /// Groups and channels have a negative ID, and a lower limit than private chats.
+    fn chat_bucket(&self, chat: &str) -> TokenBucket {
+        if chat.starts_with('-') || chat.starts_with('@') {
+            let rate = self.config.group_per_minute / 60.0;
+            TokenBucket::new(rate, self.config.group_per_minute)
+        } else {
+            let rate = self.config.private_per_second;
+            TokenBucket::new(rate, rate)
+        }
+    }
+}
+
+impl Transport for RateLimiter {
+    fn request(
+        &self,
+        token: &str,
+        request: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, TelegramError>> + Send>> {
+        let inner = self.inner.clone();
+        let token = token.to_owned();
+        Box::pin(async move { inner.send(&token, request).await })
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("transport", &self.inner.transport)
+            .field("config", &self.inner.config)
+            .finish()
+    }
+}
+
+/// A token bucket, handing out tokens at a fixed rate up to a burst capacity.
+///
+/// Tokens are reserved ahead of time, so waiting requests are served in order.
+#[derive(Debug)]
+struct TokenBucket {
+    /// The number of tokens added per second.
+    rate: f64,
+
+    /// The maximum number of tokens in the bucket.
+    capacity: f64,
+
+    /// The number of available tokens, negative if tokens are reserved ahead of time.
+    tokens: f64,
+
+    /// The time the tokens were last updated at.
+    updated_at: Instant,
+
+    /// No tokens are handed out before this time, if set.
+    blocked_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Construct a new full bucket with the given rate per second and capacity.
+    fn new(rate: f64, capacity: f64) -> Self {
+        TokenBucket {
+            rate,
+            capacity: capacity.max(1.0),
+            tokens: capacity.max(1.0),
+            updated_at: Instant::now(),
+            blocked_until: None,
+        }
+    }
+
+    /// Reserve a token, and return the time to wait until it becomes available.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        // Refill the bucket for the elapsed time
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated_at = now;
+
+        // Take a token, wait for it if there was none
+        self.tokens -= 1.0;
+        let wait = if self.tokens >= 0.0 {
+            Duration::from_millis(0)
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        };
+
+        // Wait longer if blocked
+        match self.blocked_until {
+            Some(until) if until > now => wait.max(until - now),
+            _ => wait,
+        }
+    }
+
+    /// Block handing out tokens until the given time.
+    fn block(&mut self, until: Instant) {
+        self.blocked_until = Some(self.blocked_until.map_or(until, |b| b.max(until)));
+    }
+
+    /// Check whether this bucket has been idle long enough to be full and unblocked again.
+    fn idle(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.updated_at) >= Duration::from_secs(CHAT_IDLE_SECS)
+            && self.blocked_until.map_or(true, |until| until <= now)
+    }
+}
+
+/// Check whether requests for the given API method are rate limited.
+fn is_limited(method: &str) -> bool {
+    method.starts_with("send")
+        || method.starts_with("edit")
+        || method == "forwardMessage"
+        || method == "copyMessage"
+}
+
+/// Get the chat ID the given request targets, if any.
+fn chat_id(request: &HttpRequest) -> Option<String> {
+    let body: Value = match request.body {
+        Body::Json(ref json) => serde_json::from_str(json).ok()?,
+        _ => return None,
+    };
+    match &body["chat_id"] {
+        Value::Number(id) => Some(id.to_string()),
+        Value::String(id) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+/// Get the `retry_after` time in seconds, if the given response is a flood error.
+fn retry_after(response: &HttpResponse) -> Option<u64> {
+    let body: Value = serde_json::from_slice(response.body.as_ref()?).ok()?;
+    if body["ok"].as_bool() == Some(false) && body["error_code"].as_u64() == Some(429) {
+        Some(body["parameters"]["retry_after"].as_u64().unwrap_or(1))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,66 @@
+pub mod fake;
+pub mod limiter;
+
+pub use telegram_bot::connector::Connector as Transport;
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use telegram_bot::{
+    connector::default_connector,
+    types::{HttpRequest, HttpResponse},
+    Error as TelegramError,
+};
+
+use self::fake::FakeTransport;
+use self::limiter::RateLimiter;
+use crate::config::{Config, TransportKind};
+
+/// Build the configured transport, to send Telegram API requests through.
+///
+/// The transport is wrapped in a rate limiter if enabled.
+pub fn build(config: &Config) -> Box<dyn Transport> {
+    let transport: Box<dyn Transport> = match config.telegram.transport {
+        TransportKind::Api => default_connector(),
+        TransportKind::Fake => Box::new(FakeTransport::new().verbose()),
+    };
+
+    if config.rate_limit.enabled {
+        Box::new(RateLimiter::new(transport, config.rate_limit.clone()))
+    } else {
+        transport
+    }
+}
+
+/// A transport that may be shared, to send requests through it besides the Telegram API client.
+#[derive(Clone, Debug)]
+pub struct SharedTransport(Arc<dyn Transport>);
+
+impl SharedTransport {
+    /// Share the given transport.
+    pub fn new(transport: Box<dyn Transport>) -> Self {
+        SharedTransport(Arc::from(transport))
+    }
+}
+
+impl Transport for SharedTransport {
+    fn request(
+        &self,
+        token: &str,
+        request: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, TelegramError>> + Send>> {
+        self.0.request(token, request)
+    }
+}
+
+/// Get the name of the API method the given request invokes, such as `sendMessage`.
+pub fn method_name(request: &HttpRequest) -> String {
+    request
+        .url
+        .url("")
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
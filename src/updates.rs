@@ -0,0 +1,417 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::{
+    dsl::{max, min},
+    prelude::*,
+    r2d2::PoolError,
+    result::{DatabaseErrorKind, Error as DieselError, QueryResult},
+};
+use futures::prelude::*;
+use futures::stream;
+use telegram_bot::{
+    types::{GetUpdates, Integer, Update},
+    Error as TelegramError,
+};
+use tokio::{sync::Notify, time};
+
+use crate::db::{DbPool, DbPooled};
+use crate::schema::handled_update;
+use crate::state::State;
+
+/// The long polling timeout in seconds, for which Telegram holds a request without updates.
+const POLL_TIMEOUT: Integer = 30;
+
+/// Extra time to wait for a long polling request to respond, before giving up on it.
+const POLL_GRACE: Duration = Duration::from_secs(10);
+
+/// The time to wait before polling again after an error.
+const ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// The maximum time to wait for an update to be handled before polling again, while Telegram only
+/// returns updates that are still being handled.
+const BUSY_DELAY: Duration = Duration::from_secs(1);
+
+/// The number of most recent handled updates to keep in the database.
+const KEEP_HANDLED: Integer = 10_000;
+
+/// Prune old handled updates from the database each time this many updates are handled.
+const PRUNE_INTERVAL: Integer = 100;
+
+/// Long poll the Telegram API for updates.
+///
+/// Polling resumes at the first update not handled before the bot was restarted. Updates are
+/// only acknowledged to Telegram once they and all updates before them are handled, so updates
+/// still being handled when the bot stops are fetched again.
+/// Each successful poll is recorded in the health state, also if no updates were received.
+/// Errors are yielded in the stream, after which polling continues after a short delay.
+pub fn poll(state: State) -> impl Stream<Item = Result<Update, TelegramError>> {
+    let offset = match stored_offset(state.db()) {
+        Ok(offset) => offset,
+        Err(err) => {
+            warn!(%err, "failed to load update offset, polling from the start");
+            0
+        }
+    };
+    if offset > 0 {
+        info!(offset, "resuming polling at first unhandled update");
+    }
+
+    stream::unfold(Poller::new(state, offset), |mut poller| async move {
+        loop {
+            // Yield buffered updates first
+            if let Some(update) = poller.buffer.pop_front() {
+                return Some((Ok(update), poller));
+            }
+
+            // Poll for new updates, back off on error
+            if let Err(err) = poller.fetch().await {
+                time::sleep(ERROR_DELAY).await;
+                return Some((Err(err), poller));
+            }
+        }
+    })
+}
+
+/// The state of the update poller.
+struct Poller {
+    /// The global state.
+    state: State,
+
+    /// The ID of the next update that wasn't fetched before.
+    next: Integer,
+
+    /// Updates fetched, but not yet yielded.
+    buffer: VecDeque<Update>,
+}
+
+impl Poller {
+    /// Construct a poller, fetching updates from the given offset.
+    fn new(state: State, offset: Integer) -> Self {
+        Poller {
+            state,
+            next: offset,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Fetch the next batch of updates into the buffer.
+    ///
+    /// Only acknowledges updates before the oldest update still being handled, so Telegram returns
+    /// those again. Updates fetched before are skipped. If no new updates were returned, this
+    /// waits a little for handling to progress, instead of polling again right away.
+    async fn fetch(&mut self) -> Result<(), TelegramError> {
+        let in_flight = self.state.updates();
+        let offset = in_flight
+            .oldest()
+            .map_or(self.next, |oldest| oldest.min(self.next));
+        let mut request = GetUpdates::new();
+        request.offset(offset).timeout(POLL_TIMEOUT);
+
+        let timeout = Duration::from_secs(POLL_TIMEOUT as u64) + POLL_GRACE;
+        let updates = self
+            .state
+            .telegram_client()
+            .send_timeout(request, timeout)
+            .await?;
+        self.state.health().record_poll();
+
+        // Skip updates fetched before, wait for handling if there are no others
+        let updates = updates.unwrap_or_default();
+        let fetched = updates.len();
+        let updates: Vec<Update> = updates
+            .into_iter()
+            .filter(|update| update.id >= self.next)
+            .collect();
+        if updates.is_empty() && fetched > 0 {
+            let _ = time::timeout(BUSY_DELAY, in_flight.finished.notified()).await;
+            return Ok(());
+        }
+
+        // Record and track the updates until they are handled, then buffer them
+        receive(
+            &self.state,
+            updates.iter().map(|update| update.id).collect(),
+        )
+        .await;
+        for update in updates {
+            in_flight.insert(update.id);
+            self.next = self.next.max(update.id + 1);
+            self.buffer.push_back(update);
+        }
+
+        Ok(())
+    }
+}
+
+/// Updates fetched by polling that are still being handled, by update ID.
+///
+/// Polling doesn't acknowledge updates to Telegram past the oldest of these. As Telegram returns at
+/// most 100 updates per poll, no new updates are fetched while the oldest is being handled and 99
+/// later updates were fetched already.
+pub struct InFlight {
+    /// The IDs of the updates being handled.
+    ids: Mutex<BTreeSet<Integer>>,
+
+    /// Notified each time an update is handled.
+    finished: Notify,
+}
+
+impl InFlight {
+    /// Construct a new tracker, without any updates being handled.
+    pub fn new() -> Self {
+        InFlight {
+            ids: Mutex::new(BTreeSet::new()),
+            finished: Notify::new(),
+        }
+    }
+
+    /// Track the given update as being handled.
+    fn insert(&self, update_id: Integer) {
+        self.ids.lock().unwrap().insert(update_id);
+    }
+
+    /// Get the oldest update that is still being handled, if any.
+    fn oldest(&self) -> Option<Integer> {
+        self.ids.lock().unwrap().iter().next().copied()
+    }
+
+    /// Track the given update as being handled until the returned guard is dropped, also if
+    /// handling panics.
+    pub fn guard(&self, update_id: Integer) -> InFlightGuard<'_> {
+        InFlightGuard {
+            in_flight: self,
+            update_id,
+        }
+    }
+}
+
+/// Stops tracking an update as being handled when dropped.
+pub struct InFlightGuard<'a> {
+    /// The tracker the update is tracked in.
+    in_flight: &'a InFlight,
+
+    /// The ID of the update.
+    update_id: Integer,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .ids
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&self.update_id);
+        self.in_flight.finished.notify_one();
+    }
+}
+
+/// Record the given updates as received, but not handled yet.
+///
+/// Updates that are not handled before a restart are fetched again, starting at the oldest of
+/// these. Updates recorded before are left as-is. If the
+/// updates can't be recorded, they are handled anyway.
+async fn receive(state: &State, update_ids: Vec<Integer>) {
+    if update_ids.is_empty() {
+        return;
+    }
+    let result = state
+        .db_query(move |connection| {
+            for update_id in update_ids {
+                let inserted = with_connection!(connection, |conn| diesel::insert_into(
+                    handled_update::table
+                )
+                .values((
+                    handled_update::update_id.eq(update_id),
+                    handled_update::handled.eq(false),
+                ))
+                .execute(conn));
+                match inserted {
+                    Ok(_)
+                    | Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(())
+        })
+        .await;
+    if let Err(err) = result {
+        warn!(%err, "failed to record received updates, handling them anyway");
+    }
+}
+
+/// Check whether the given update still has to be handled.
+///
+/// Returns `false` if the update was handled before, such as before a restart, in which case it
+/// must be skipped. If this can't be checked, the update is handled anyway.
+pub async fn pending(state: &State, update_id: Integer) -> bool {
+    let result = state
+        .db_query(move |connection| {
+            with_connection!(connection, |conn| handled_update::table
+                .find(update_id)
+                .select(handled_update::handled)
+                .first::<bool>(conn)
+                .optional())
+        })
+        .await;
+    match result {
+        Ok(handled) => handled != Some(true),
+        Err(err) => {
+            warn!(%err, "failed to check whether update was handled, handling it anyway");
+            true
+        }
+    }
+}
+
+/// Mark the given update as handled, after handling it completed.
+///
+/// The update is skipped if it is received again. Failures are logged.
+pub async fn finish(state: &State, update_id: Integer) {
+    let result = state
+        .db_query(move |connection| finish_update(connection, update_id))
+        .await;
+    if let Err(err) = result {
+        warn!(%err, "failed to mark update as handled");
+    }
+}
+
+/// Store the given update as handled, also if it wasn't recorded when received.
+///
+/// Old updates are pruned once in a while, also those that never completed handling.
+fn finish_update(connection: &DbPooled, update_id: Integer) -> QueryResult<()> {
+    with_connection!(connection, |conn| {
+        let updated = diesel::update(handled_update::table.find(update_id))
+            .set((
+                handled_update::handled.eq(true),
+                handled_update::handled_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+        if updated == 0 {
+            match diesel::insert_into(handled_update::table)
+                .values(handled_update::update_id.eq(update_id))
+                .execute(conn)
+            {
+                Ok(_) | Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        if update_id % PRUNE_INTERVAL == 0 {
+            diesel::delete(
+                handled_update::table
+                    .filter(handled_update::update_id.le(update_id - KEEP_HANDLED)),
+            )
+            .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// Get the update offset to resume polling from.
+///
+/// This is the oldest update that was received but not handled, to fetch it again, or the update
+/// after the last handled one if there is none. Returns `0` if no update was received yet.
+pub fn stored_offset(db: &DbPool) -> Result<Integer, Error> {
+    let connection = db.get().map_err(Error::Connect)?;
+    let unhandled = with_connection!(connection, |conn| handled_update::table
+        .filter(handled_update::handled.eq(false))
+        .select(min(handled_update::update_id))
+        .first::<Option<i64>>(conn))
+    .map_err(Error::Query)?;
+    let last = with_connection!(connection, |conn| handled_update::table
+        .select(max(handled_update::update_id))
+        .first::<Option<i64>>(conn))
+    .map_err(Error::Query)?;
+    Ok(unhandled.or_else(|| last.map(|id| id + 1)).unwrap_or(0))
+}
+
+/// An update tracking error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to get a database connection from the pool.
+    #[fail(display = "failed to get database connection from pool")]
+    Connect(#[cause] PoolError),
+
+    /// Failed to query the handled updates.
+    #[fail(display = "failed to query handled updates")]
+    Query(#[cause] DieselError),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{finish, pending, receive, stored_offset, Poller};
+    use crate::testing::{self, Harness, GROUP_ID};
+
+    #[tokio::test]
+    async fn resumes_at_first_unhandled_update() {
+        let harness = Harness::new().await;
+        let state = &harness.state;
+        assert_eq!(stored_offset(state.db()).unwrap(), 0);
+
+        // Handling completes out of order
+        receive(state, vec![10, 11, 12]).await;
+        finish(state, 12).await;
+        finish(state, 10).await;
+        assert_eq!(stored_offset(state.db()).unwrap(), 11);
+
+        finish(state, 11).await;
+        assert_eq!(stored_offset(state.db()).unwrap(), 13);
+    }
+
+    #[tokio::test]
+    async fn skips_handled_updates() {
+        let harness = Harness::new().await;
+        let state = &harness.state;
+
+        receive(state, vec![20]).await;
+        assert!(pending(state, 20).await);
+        finish(state, 20).await;
+        assert!(!pending(state, 20).await);
+
+        // Receiving an update again doesn't reset it
+        receive(state, vec![20]).await;
+        assert!(!pending(state, 20).await);
+        assert!(pending(state, 21).await);
+    }
+
+    #[tokio::test]
+    async fn holds_offset_at_oldest_update_being_handled() {
+        let harness = Harness::new().await;
+        let state = &harness.state;
+        let updates = json!([
+            testing::update_json(5, GROUP_ID, "first"),
+            testing::update_json(6, GROUP_ID, "second"),
+        ]);
+        let mut poller = Poller::new(state.clone(), 5);
+
+        harness
+            .transport
+            .script_result("getUpdates", updates.clone());
+        poller.fetch().await.unwrap();
+        assert_eq!(poller.buffer.len(), 2);
+        let first = state.updates().guard(5);
+        let second = state.updates().guard(6);
+
+        // Handling completes out of order, the first update is fetched again but not yielded
+        drop(second);
+        harness.transport.script_result("getUpdates", updates);
+        poller.fetch().await.unwrap();
+        assert_eq!(poller.buffer.len(), 2);
+
+        drop(first);
+        harness.transport.script_result("getUpdates", json!([]));
+        poller.fetch().await.unwrap();
+
+        let offsets: Vec<_> = harness
+            .transport
+            .requests_for("getUpdates")
+            .into_iter()
+            .map(|request| request.body["offset"].clone())
+            .collect();
+        assert_eq!(offsets, vec![json!(5), json!(5), json!(7)]);
+    }
+}
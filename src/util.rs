@@ -1,5 +1,6 @@
 extern crate colored;
 
+use std::any::Any;
 use std::borrow::Borrow;
 
 use self::colored::*;
@@ -7,14 +8,15 @@ use failure::Fail;
 use telegram_bot::{
     prelude::*,
     types::{Message, ParseMode},
-    Error as TelegramError,
 };
 
+use crate::send::Error as SendError;
 use crate::state::State;
+use crate::stats::TelegramToI64;
 
 /// Print the given error in a proper format for the user,
 /// with it's causes.
-pub fn _print_error<E: Fail>(err: impl Borrow<E>) {
+pub fn print_error<E: Fail>(err: impl Borrow<E>) {
     // Report each printable error, count them
     let count = err
         .borrow()
@@ -24,21 +26,21 @@ pub fn _print_error<E: Fail>(err: impl Borrow<E>) {
         .enumerate()
         .inspect(|(i, err)| {
             if *i == 0 {
-                eprintln!("{} {}", _highlight_error("error:"), err);
+                eprintln!("{} {}", highlight_error("error:"), err);
             } else {
-                eprintln!("{} {}", _highlight_error("caused by:"), err);
+                eprintln!("{} {}", highlight_error("caused by:"), err);
             }
         })
         .count();
 
     // Fall back to a basic message
     if count == 0 {
-        eprintln!("{} an undefined error occurred", _highlight_error("error:"),);
+        eprintln!("{} an undefined error occurred", highlight_error("error:"),);
     }
 }
 
 /// Highlight the given text with an error color.
-pub fn _highlight_error(msg: &str) -> ColoredString {
+pub fn highlight_error(msg: &str) -> ColoredString {
     msg.red().bold()
 }
 
@@ -74,19 +76,95 @@ pub fn format_error<E: Fail>(err: impl Borrow<E>) -> String {
     msg
 }
 
-/// Handle a message error, by sending the occurred error to the user as a reply on their
-/// message along with it's causes.
-// TODO: create a future for this, delay it for a second to cool down from throttling
+/// Format the causes of the given error on a single line, for logging.
+pub fn error_chain(err: &dyn Fail) -> String {
+    let chain: Vec<String> = err
+        .iter_chain()
+        .map(|err| format!("{}", err))
+        .filter(|err| !err.is_empty())
+        .collect();
+    if chain.is_empty() {
+        "an undefined error occurred".into()
+    } else {
+        chain.join(": ")
+    }
+}
+
+/// Get the message of a panic from its payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".into())
+}
+
+/// Get the Levenshtein edit distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            let next = (row[j + 1] + 1).min(row[j] + 1).min(diagonal + cost);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// Escape the given text for use in a legacy Markdown message, such as names with underscores.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if let '_' | '*' | '`' | '[' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Handle a message error, by logging it and replying to the user.
+///
+/// The full error is logged under a correlation ID. Bot owners get the error with all causes as
+/// reply, other users get a short message with the correlation ID only. Error replies are
+/// throttled and deduplicated per chat, suppressed errors are only logged.
 pub async fn handle_msg_error<E: Fail>(
     state: State,
     msg: Message,
     err: impl Borrow<E>,
-) -> Result<(), TelegramError> {
-    state
-        .telegram_send(
-            msg.text_reply(format_error(err))
-                .parse_mode(ParseMode::Markdown),
+) -> Result<(), SendError> {
+    let err = err.borrow();
+    let error_id = state.errors().correlation_id();
+    let chain = error_chain(err);
+    error!(%error_id, error = %chain, "failed to handle message");
+
+    // Throttle error replies in this chat
+    let config = &state.config().errors;
+    if !state
+        .errors()
+        .should_reply(config, msg.chat.id().to_i64(), &chain)
+    {
+        debug!(%error_id, "suppressed error reply");
+        return Ok(());
+    }
+
+    // Only show error details to bot owners
+    let text = if state.config().bot.is_owner(msg.from.id.to_i64()) {
+        format!("{}\n\nError ID: `{}`", format_error::<E>(err), error_id)
+    } else {
+        format!(
+            "Whoops! An error occurred while processing your message. 😱\n\nError ID: `{}`",
+            error_id,
         )
+    };
+
+    state
+        .send(msg.text_reply(text).parse_mode(ParseMode::Markdown))
         .await
         .map(|_| ())
 }
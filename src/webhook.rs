@@ -0,0 +1,201 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use failure::SyncFailure;
+use hyper::{
+    header::CONTENT_LENGTH,
+    service::{make_service_fn, service_fn},
+    Body, Error as HyperError, Method, Request, Response, Server, StatusCode,
+};
+use telegram_bot::{types::Update, Error as TelegramError};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::requests::{DeleteWebhook, SetWebhook};
+use crate::state::State;
+
+/// The header Telegram sends the configured secret token in.
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// The maximum accepted size of a webhook request body in bytes.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// The number of received updates to buffer, before holding back webhook responses.
+const BUFFER_SIZE: usize = 64;
+
+/// Start receiving updates through a webhook.
+///
+/// This registers the webhook with Telegram if a public URL is configured, and spawns an HTTP
+/// server on the given runtime to receive webhook requests on.
+/// A stream of received updates is returned, to feed into the regular update handling.
+pub async fn updates(state: &State, handle: &Handle) -> Result<ReceiverStream<Update>, Error> {
+    let config = &state.config().webhook;
+
+    // Register the webhook with Telegram if a public URL is configured
+    if let Some(url) = &config.url {
+        state
+            .telegram_client()
+            .send(SetWebhook::new(url.clone(), config.secret_token.clone()))
+            .await
+            .map_err(|err| Error::Register(SyncFailure::new(err)))?;
+    }
+
+    // Bind the server, and spawn it on the runtime
+    let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
+    let context = Arc::new(Context {
+        path: config.path.clone(),
+        secret_token: config.secret_token.clone(),
+        sender,
+    });
+    let server = Server::try_bind(&config.listen)
+        .map_err(|err| Error::Bind(config.listen, err))?
+        .serve(make_service_fn(move |_| {
+            let context = context.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| handle_request(context.clone(), req)))
+            }
+        }));
+    state.health().record_webhook(true);
+    let health_state = state.clone();
+    handle.spawn(async move {
+        if let Err(err) = server.await {
+            error!(%err, "webhook server failed");
+        }
+        health_state.health().record_webhook(false);
+    });
+
+    info!(listen = %config.listen, "listening for webhook updates");
+
+    Ok(ReceiverStream::new(receiver))
+}
+
+/// Remove a webhook registered with Telegram before, if any.
+///
+/// Telegram refuses polling for updates while a webhook is registered, such as after switching
+/// from webhook to polling mode. Pending updates are kept, to poll them next.
+pub async fn delete(state: &State) -> Result<(), Error> {
+    state
+        .telegram_client()
+        .send(DeleteWebhook::new())
+        .await
+        .map_err(|err| Error::Delete(SyncFailure::new(err)))
+}
+
+/// Context shared by webhook requests.
+struct Context {
+    /// The HTTP path webhook requests are accepted on.
+    path: String,
+
+    /// The secret token that must be sent along with each request.
+    secret_token: Option<String>,
+
+    /// The sender to push received updates into.
+    sender: Sender<Update>,
+}
+
+/// Handle a single webhook request.
+///
+/// The update is only acknowledged after it has been queued for handling, so Telegram retries
+/// delivery if the bot is not accepting updates.
+async fn handle_request(
+    context: Arc<Context>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    // Only accept posts on the webhook path
+    if req.uri().path() != context.path {
+        return Ok(respond(StatusCode::NOT_FOUND));
+    }
+    if req.method() != Method::POST {
+        return Ok(respond(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    // Validate the secret token
+    let token = req
+        .headers()
+        .get(SECRET_TOKEN_HEADER)
+        .map(|token| token.as_bytes());
+    match (&context.secret_token, token) {
+        (Some(secret), Some(token)) if secure_eq(secret.as_bytes(), token) => {}
+        _ => return Ok(respond(StatusCode::UNAUTHORIZED)),
+    }
+
+    // Refuse overly large bodies
+    let length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok());
+    match length {
+        Some(length) if length <= MAX_BODY_SIZE => {}
+        Some(_) => return Ok(respond(StatusCode::PAYLOAD_TOO_LARGE)),
+        None => return Ok(respond(StatusCode::LENGTH_REQUIRED)),
+    }
+
+    // Decode the update
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(err) => {
+            warn!(%err, "failed to read webhook request body");
+            return Ok(respond(StatusCode::BAD_REQUEST));
+        }
+    };
+    let update: Update = match serde_json::from_slice(&body) {
+        Ok(update) => update,
+        Err(err) => {
+            warn!(%err, "failed to decode webhook update, ignoring");
+            return Ok(respond(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    // Queue the update for handling
+    match context.sender.send(update).await {
+        Ok(()) => Ok(respond(StatusCode::OK)),
+        Err(_) => Ok(respond(StatusCode::SERVICE_UNAVAILABLE)),
+    }
+}
+
+/// Build an empty response with the given status code.
+fn respond(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Compare two byte strings in constant time, to not leak the secret token through timing.
+fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// A webhook error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to register the webhook with Telegram.
+    #[fail(display = "failed to register webhook with Telegram")]
+    Register(#[cause] SyncFailure<TelegramError>),
+
+    /// Failed to remove the webhook from Telegram, to poll for updates.
+    #[fail(display = "failed to remove webhook from Telegram to poll for updates")]
+    Delete(#[cause] SyncFailure<TelegramError>),
+
+    /// Failed to bind the webhook server to the configured address.
+    #[fail(display = "failed to bind webhook server to {}", _0)]
+    Bind(SocketAddr, #[cause] HyperError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::delete;
+    use crate::testing::Harness;
+
+    #[tokio::test]
+    async fn deletes_webhook_keeping_pending_updates() {
+        let harness = Harness::new().await;
+        delete(&harness.state).await.unwrap();
+
+        let requests = harness.transport.requests_for("deleteWebhook");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].body["drop_pending_updates"], false);
+    }
+}